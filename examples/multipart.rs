@@ -1,6 +1,6 @@
 use std::{fs::{self, File, OpenOptions}, path::PathBuf, cell::RefCell, io::{Write, self}};
 
-use stream_unpack::zip::{ZipUnpacker, ZipDecodedData, read_cd};
+use stream_unpack::zip::{ZipUnpacker, ZipDecodedData, read_cd, structures::attributes::EntryKind};
 
 fn main() {
     let output_dir = "unpack";
@@ -37,9 +37,14 @@ fn main() {
                 println!();
 
                 let mut path = PathBuf::from(output_dir);
-                path.push(&cdfh.filename);
+                path.push(cdfh.sanitized_path()?);
 
-                if !cdfh.is_directory() {
+                if cdfh.entry_kind() == EntryKind::Symlink {
+                    print!("New symlink: {}", cdfh.filename);
+                    io::stdout().flush()?;
+
+                    *current_file.borrow_mut() = None;
+                } else if !cdfh.is_directory() {
                     print!("New file: {}", cdfh.filename);
                     io::stdout().flush()?;
 
@@ -49,6 +54,7 @@ fn main() {
                         OpenOptions::new()
                         .create(true)
                         .write(true)
+                        .truncate(true)
                         .open(path)?
                     );
                 } else {
@@ -64,7 +70,21 @@ fn main() {
                 io::stdout().flush()?;
 
                 current_file.borrow().as_ref().unwrap().write_all(data)?;
-            }
+            },
+
+            ZipDecodedData::Symlink { target, .. } => {
+                // Symbolic links are not created, since a link pointing outside of the
+                // output directory would allow later entries to be written through it
+                print!(" -> {target} (skipped)");
+                io::stdout().flush()?;
+            },
+
+            ZipDecodedData::HeaderMismatch(cdfh, fields) => {
+                println!();
+                print!("Warning: headers of {} differ: {fields:?}", cdfh.filename);
+            },
+
+            _ => {}
         }
 
         Ok(())
@@ -0,0 +1,22 @@
+/// Provides a [Decryptor] for the traditional PKWARE encryption (ZipCrypto)
+pub mod zipcrypto;
+
+/// Provides a [Decryptor] for the WinZip AES encryption
+#[cfg(feature = "zip-aes")]
+pub mod winzip_aes;
+
+pub trait Decryptor: std::fmt::Debug + Send + Sync {
+    /// Decrypts data in place. Every byte must be passed exactly once
+    fn decrypt(&mut self, data: &mut [u8]);
+
+    /// The size of the trailer (e.g. an authentication code) that
+    /// follows the encrypted data. Included in the compressed size
+    fn trailer_size(&self) -> usize {
+        0
+    }
+
+    /// Verifies the trailer after all data was decrypted
+    fn verify_trailer(&self, _trailer: &[u8]) -> bool {
+        true
+    }
+}
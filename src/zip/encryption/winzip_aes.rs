@@ -0,0 +1,173 @@
+use std::fmt::Debug;
+
+use aes::{Aes128, Aes192, Aes256};
+use ctr::{Ctr128LE, cipher::{KeyIvInit, StreamCipher}};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+
+use super::Decryptor;
+
+pub const AES_PASSWORD_VERIFIER_SIZE: usize = 2;
+pub const AES_AUTHENTICATION_CODE_SIZE: usize = 10;
+pub const AES_KEY_DERIVATION_ROUNDS: u32 = 1000;
+
+/// A [Decryptor] for the WinZip AES encryption method (AE-1 and AE-2)
+pub struct AesDecryptor {
+    cipher: Box<dyn StreamCipher + Send + Sync>,
+    mac: Hmac<Sha1>
+}
+
+impl Debug for AesDecryptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AesDecryptor")
+            .finish()
+    }
+}
+
+impl Decryptor for AesDecryptor {
+    fn decrypt(&mut self, data: &mut [u8]) {
+        // The authentication code is calculated over the encrypted data
+        self.mac.update(data);
+        self.cipher.apply_keystream(data);
+    }
+
+    fn trailer_size(&self) -> usize {
+        AES_AUTHENTICATION_CODE_SIZE
+    }
+
+    fn verify_trailer(&self, trailer: &[u8]) -> bool {
+        self.mac.clone()
+            .verify_truncated_left(trailer)
+            .is_ok()
+    }
+}
+
+impl AesDecryptor {
+    /// Derives the keys from the password and the salt at the start of "header",
+    /// which must be followed by the password verification value. "key_size"
+    /// must be 16, 24 or 32 bytes, and the salt is half of it
+    /// 
+    /// Returns None if the password verification value does not match,
+    /// which means that the password is wrong
+    pub fn from_header(password: impl AsRef<[u8]>, key_size: usize, header: &[u8]) -> Option<Self> {
+        let salt_size = key_size / 2;
+        let salt = &header[..salt_size];
+        let verifier = &header[salt_size..(salt_size + AES_PASSWORD_VERIFIER_SIZE)];
+
+        let mut derived = vec![0; key_size * 2 + AES_PASSWORD_VERIFIER_SIZE];
+        pbkdf2::pbkdf2_hmac::<Sha1>(password.as_ref(), salt, AES_KEY_DERIVATION_ROUNDS, &mut derived);
+
+        let (encryption_key, rest) = derived.split_at(key_size);
+        let (authentication_key, derived_verifier) = rest.split_at(key_size);
+        if derived_verifier != verifier {
+            return None;
+        }
+
+        // The counter is little endian and starts at 1
+        let iv = 1u128.to_le_bytes();
+        let cipher: Box<dyn StreamCipher + Send + Sync> = match key_size {
+            16 => Box::new(Ctr128LE::<Aes128>::new_from_slices(encryption_key, &iv).ok()?),
+            24 => Box::new(Ctr128LE::<Aes192>::new_from_slices(encryption_key, &iv).ok()?),
+            32 => Box::new(Ctr128LE::<Aes256>::new_from_slices(encryption_key, &iv).ok()?),
+            _ => return None
+        };
+
+        let mac = Hmac::<Sha1>::new_from_slice(authentication_key).ok()?;
+
+        Some(Self {
+            cipher,
+            mac
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AesDecryptor;
+    use crate::zip::encryption::Decryptor;
+
+    // Encrypted with the password "secret". The keys are derived with PBKDF2-HMAC-SHA1,
+    // and the keystream uses a little endian counter starting at 1 (three blocks)
+    const PLAINTEXT: &[u8; 43] = b"Hello, WinZip AES! This spans three blocks.";
+
+    const AES128_HEADER: [u8; 10] = [0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x00, 0xba];
+    const AES128_CIPHERTEXT: [u8; 43] = [
+        0x82, 0x05, 0x26, 0x61, 0x6e, 0x11, 0x1d, 0x94, 0xa5, 0x08, 0x2e, 0x62, 0xe8, 0xa9, 0x47, 0x68,
+        0xec, 0x66, 0xcb, 0x6c, 0x17, 0x69, 0x11, 0x4f, 0x7c, 0x65, 0x89, 0xe1, 0xe4, 0x39, 0x68, 0x9b,
+        0x15, 0xcf, 0x01, 0xee, 0x0e, 0x20, 0x4e, 0x86, 0x41, 0xe5, 0x47
+    ];
+    const AES128_MAC: [u8; 10] = [0x76, 0x7b, 0x1b, 0xb7, 0x02, 0x68, 0x77, 0x13, 0x2b, 0xbf];
+
+    const AES256_HEADER: [u8; 18] = [
+        0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d, 0x1e, 0x1f,
+        0xe6, 0x89
+    ];
+    const AES256_CIPHERTEXT: [u8; 43] = [
+        0x5c, 0xec, 0x10, 0xfa, 0xfc, 0xd1, 0x83, 0x24, 0x3b, 0x2b, 0xb4, 0x49, 0xee, 0x70, 0x60, 0xc2,
+        0xd4, 0x1f, 0x68, 0x64, 0x0b, 0x73, 0xef, 0xe7, 0x81, 0x10, 0xf8, 0x7c, 0x9e, 0xef, 0x53, 0xaf,
+        0x40, 0x49, 0xb0, 0x07, 0xe9, 0x94, 0xfc, 0x0d, 0x2d, 0x1b, 0x78
+    ];
+    const AES256_MAC: [u8; 10] = [0xf1, 0x9e, 0x86, 0xf6, 0x66, 0xc1, 0x9a, 0x80, 0x49, 0x86];
+
+    #[test]
+    fn decrypts_known_ciphertext() {
+        for (key_size, header, ciphertext, mac) in [
+            (16, &AES128_HEADER[..], AES128_CIPHERTEXT, AES128_MAC),
+            (32, &AES256_HEADER[..], AES256_CIPHERTEXT, AES256_MAC)
+        ] {
+            let mut decryptor = AesDecryptor::from_header("secret", key_size, header).unwrap();
+
+            let mut data = ciphertext;
+            decryptor.decrypt(&mut data);
+            assert_eq!(&data, PLAINTEXT);
+            assert!(decryptor.verify_trailer(&mac));
+        }
+    }
+
+    #[test]
+    fn decrypts_in_chunks() {
+        let mut decryptor = AesDecryptor::from_header("secret", 32, &AES256_HEADER).unwrap();
+
+        // The chunks don't line up with the cipher blocks
+        let mut data = AES256_CIPHERTEXT;
+        for chunk in data.chunks_mut(7) {
+            decryptor.decrypt(chunk);
+        }
+
+        assert_eq!(&data, PLAINTEXT);
+        assert!(decryptor.verify_trailer(&AES256_MAC));
+    }
+
+    #[test]
+    fn rejects_wrong_password() {
+        assert!(AesDecryptor::from_header("secreT", 16, &AES128_HEADER).is_none());
+        assert!(AesDecryptor::from_header("secreT", 32, &AES256_HEADER).is_none());
+    }
+
+    #[test]
+    fn rejects_wrong_verifier() {
+        let mut header = AES256_HEADER;
+        header[17] ^= 1;
+        assert!(AesDecryptor::from_header("secret", 32, &header).is_none());
+    }
+
+    #[test]
+    fn rejects_mac_mismatch() {
+        // Modified authentication code
+        let mut decryptor = AesDecryptor::from_header("secret", 32, &AES256_HEADER).unwrap();
+        let mut data = AES256_CIPHERTEXT;
+        decryptor.decrypt(&mut data);
+
+        let mut mac = AES256_MAC;
+        mac[9] ^= 1;
+        assert!(!decryptor.verify_trailer(&mac));
+
+        // Modified ciphertext
+        let mut decryptor = AesDecryptor::from_header("secret", 32, &AES256_HEADER).unwrap();
+        let mut data = AES256_CIPHERTEXT;
+        data[0] ^= 1;
+        decryptor.decrypt(&mut data);
+
+        assert!(!decryptor.verify_trailer(&AES256_MAC));
+    }
+}
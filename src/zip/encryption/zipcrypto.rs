@@ -0,0 +1,150 @@
+use std::fmt::Debug;
+
+use super::Decryptor;
+
+pub const ZIPCRYPTO_HEADER_SIZE: usize = 12;
+
+const CRC32_TABLE: [u32; 256] = make_crc32_table();
+
+const fn make_crc32_table() -> [u32; 256] {
+    let mut table = [0; 256];
+
+    let mut i = 0;
+    while i < 256 {
+        let mut value = i as u32;
+
+        let mut bit = 0;
+        while bit < 8 {
+            value = if value & 1 != 0 {
+                0xEDB88320 ^ (value >> 1)
+            } else {
+                value >> 1
+            };
+
+            bit += 1;
+        }
+
+        table[i] = value;
+        i += 1;
+    }
+
+    table
+}
+
+#[inline]
+fn crc32_update(crc: u32, byte: u8) -> u32 {
+    (crc >> 8) ^ CRC32_TABLE[((crc ^ byte as u32) & 0xFF) as usize]
+}
+
+/// A [Decryptor] for the traditional PKWARE encryption method
+#[derive(Clone)]
+pub struct ZipCryptoDecryptor {
+    keys: [u32; 3]
+}
+
+impl Debug for ZipCryptoDecryptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ZipCryptoDecryptor")
+            .finish()
+    }
+}
+
+impl Decryptor for ZipCryptoDecryptor {
+    fn decrypt(&mut self, data: &mut [u8]) {
+        for byte in data {
+            *byte ^= self.key_byte();
+            self.update_keys(*byte);
+        }
+    }
+}
+
+impl ZipCryptoDecryptor {
+    /// Creates a new ZipCryptoDecryptor with keys initialized from the password
+    pub fn new(password: impl AsRef<[u8]>) -> Self {
+        let mut decryptor = Self {
+            keys: [0x12345678, 0x23456789, 0x34567890]
+        };
+
+        for byte in password.as_ref() {
+            decryptor.update_keys(*byte);
+        }
+
+        decryptor
+    }
+
+    /// Creates a new ZipCryptoDecryptor and decrypts the encryption header.
+    /// "check_byte" is the high byte of the CRC-32 of the file, or of the
+    /// modification time if the file has a data descriptor
+    /// 
+    /// Returns None if the last byte of the header does not match "check_byte",
+    /// which means that the password is wrong
+    pub fn from_header(password: impl AsRef<[u8]>, header: &[u8; ZIPCRYPTO_HEADER_SIZE], check_byte: u8) -> Option<Self> {
+        let mut decryptor = Self::new(password);
+
+        let mut header = *header;
+        decryptor.decrypt(&mut header);
+
+        if header[ZIPCRYPTO_HEADER_SIZE - 1] == check_byte {
+            Some(decryptor)
+        } else {
+            None
+        }
+    }
+
+    fn update_keys(&mut self, byte: u8) {
+        self.keys[0] = crc32_update(self.keys[0], byte);
+        self.keys[1] = self.keys[1]
+            .wrapping_add(self.keys[0] & 0xFF)
+            .wrapping_mul(134775813)
+            .wrapping_add(1);
+        self.keys[2] = crc32_update(self.keys[2], (self.keys[1] >> 24) as u8);
+    }
+
+    fn key_byte(&self) -> u8 {
+        let temp = (self.keys[2] | 2) & 0xFFFF;
+        (temp.wrapping_mul(temp ^ 1) >> 8) as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ZipCryptoDecryptor, ZIPCRYPTO_HEADER_SIZE};
+    use crate::zip::encryption::Decryptor;
+
+    // "Hello, ZipCrypto!" encrypted by Info-ZIP with the password "secret". The file has
+    // a data descriptor, so the check byte is the high byte of the modification time
+    const HEADER: [u8; ZIPCRYPTO_HEADER_SIZE] = [0x6a, 0xb4, 0xcb, 0x44, 0x8a, 0x3b, 0x7d, 0xe4, 0x0e, 0x97, 0xea, 0xd5];
+    const CIPHERTEXT: [u8; 17] = [0xda, 0xfb, 0xaa, 0x44, 0xb3, 0x24, 0x23, 0xc0, 0x8a, 0x2d, 0xe5, 0x02, 0x13, 0x4a, 0x14, 0x48, 0x90];
+    const CHECK_BYTE: u8 = 0xbb;
+
+    #[test]
+    fn decrypts_known_ciphertext() {
+        let mut decryptor = ZipCryptoDecryptor::from_header("secret", &HEADER, CHECK_BYTE).unwrap();
+
+        let mut data = CIPHERTEXT;
+        decryptor.decrypt(&mut data);
+        assert_eq!(&data, b"Hello, ZipCrypto!");
+    }
+
+    #[test]
+    fn decrypts_in_chunks() {
+        let mut decryptor = ZipCryptoDecryptor::from_header("secret", &HEADER, CHECK_BYTE).unwrap();
+
+        let mut data = CIPHERTEXT;
+        let (first, second) = data.split_at_mut(5);
+        decryptor.decrypt(first);
+        decryptor.decrypt(second);
+        assert_eq!(&data, b"Hello, ZipCrypto!");
+    }
+
+    #[test]
+    fn rejects_wrong_password() {
+        assert!(ZipCryptoDecryptor::from_header("secreT", &HEADER, CHECK_BYTE).is_none());
+    }
+
+    #[test]
+    fn rejects_wrong_check_byte() {
+        // The high byte of the CRC-32, which is not used if there is a data descriptor
+        assert!(ZipCryptoDecryptor::from_header("secret", &HEADER, 0x81).is_none());
+    }
+}
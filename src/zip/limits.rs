@@ -0,0 +1,84 @@
+use super::{structures::central_directory::CentralDirectoryFileHeader, DecoderError};
+
+/// A resource limit, see [ZipLimits]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ZipLimit {
+    TotalUncompressedSize,
+    EntryUncompressedSize,
+    CompressionRatio,
+    EntryCount,
+    FilenameLength
+}
+
+/// Resource limits for unpacking untrusted archives. Limits that are
+/// None are not enforced
+#[derive(Debug, Clone, Default)]
+pub struct ZipLimits {
+    /// The maximum amount of bytes decoded from all files
+    pub max_total_uncompressed_size: Option<u64>,
+
+    /// The maximum amount of bytes decoded from a single file
+    pub max_entry_uncompressed_size: Option<u64>,
+
+    /// The maximum ratio of decoded bytes to compressed bytes of a single file
+    pub max_compression_ratio: Option<u64>,
+
+    /// The maximum amount of files in the archive
+    pub max_entry_count: Option<usize>,
+
+    /// The maximum length of a filename in bytes
+    pub max_filename_length: Option<usize>
+}
+
+impl ZipLimits {
+    /// Checks the declared sizes of the files against the limits
+    pub(crate) fn check_headers(&self, headers: &[CentralDirectoryFileHeader]) -> Result<(), DecoderError> {
+        if self.max_entry_count.is_some_and(|max| headers.len() > max) {
+            return Err(DecoderError::LimitExceeded { limit: ZipLimit::EntryCount, entry: None });
+        }
+
+        let mut total_size = 0u64;
+        for cdfh in headers {
+            if self.max_filename_length.is_some_and(|max| cdfh.filename_raw.len() > max) {
+                return Err(exceeded(ZipLimit::FilenameLength, cdfh));
+            }
+
+            self.check_entry(cdfh, cdfh.compressed_size, cdfh.uncompressed_size)?;
+
+            total_size = total_size.saturating_add(cdfh.uncompressed_size);
+            self.check_total(total_size)?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks the amount of bytes decoded from a single file so far
+    pub(crate) fn check_entry(&self, cdfh: &CentralDirectoryFileHeader, compressed_size: u64, uncompressed_size: u64) -> Result<(), DecoderError> {
+        if self.max_entry_uncompressed_size.is_some_and(|max| uncompressed_size > max) {
+            return Err(exceeded(ZipLimit::EntryUncompressedSize, cdfh));
+        }
+
+        if self.max_compression_ratio.is_some_and(|max| uncompressed_size > max.saturating_mul(compressed_size.max(1))) {
+            return Err(exceeded(ZipLimit::CompressionRatio, cdfh));
+        }
+
+        Ok(())
+    }
+
+    /// Checks the amount of bytes decoded from all files so far
+    pub(crate) fn check_total(&self, total_size: u64) -> Result<(), DecoderError> {
+        if self.max_total_uncompressed_size.is_some_and(|max| total_size > max) {
+            return Err(DecoderError::LimitExceeded { limit: ZipLimit::TotalUncompressedSize, entry: None });
+        }
+
+        Ok(())
+    }
+}
+
+fn exceeded(limit: ZipLimit, cdfh: &CentralDirectoryFileHeader) -> DecoderError {
+    DecoderError::LimitExceeded {
+        limit,
+        entry: Some(cdfh.filename.clone())
+    }
+}
@@ -1,8 +1,12 @@
+use crc32fast::Hasher;
 use thiserror::Error;
 
 use crate::decompress::{Decompressor, DecompressionError};
 
-use self::structures::{local_file_header::{LocalFileHeader, LFH_SIGNATURE, LFH_CONSTANT_SIZE}, DecompressorCreationError, central_directory::{CentralDirectoryFileHeader, SortedCentralDirectory}};
+#[cfg(feature = "zip-aes")]
+use self::encryption::winzip_aes::{AesDecryptor, AES_PASSWORD_VERIFIER_SIZE};
+
+use self::{limits::{ZipLimits, ZipLimit}, encryption::{Decryptor, zipcrypto::{ZipCryptoDecryptor, ZIPCRYPTO_HEADER_SIZE}}, structures::{local_file_header::{LocalFileHeader, HeaderField, LFH_SIGNATURE, LFH_CONSTANT_SIZE}, CompressionMethod, DecompressorCreationError, central_directory::{CentralDirectoryFileHeader, SortedCentralDirectory}, data_descriptor::DataDescriptor, attributes::EntryKind, encoding::decode_header_text, file_header::{FLAG_UTF8, UnsupportedFeature}}};
 
 /// Provides utilities for wokring with ZIP structures 
 pub mod structures;
@@ -10,7 +14,14 @@ pub mod structures;
 /// Provides utilities for automatically locating and reading a central directory
 pub mod read_cd;
 
+/// Provides utilities for decrypting encrypted ZIP files
+pub mod encryption;
+
+/// Provides resource limits for untrusted archives
+pub mod limits;
+
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum DecoderError {
     #[error("failed to decompress: {0}")]
     Decompression(#[from] DecompressionError),
@@ -30,6 +41,46 @@ pub enum DecoderError {
     #[error("file header has an invalid signature")]
     InvalidSignature,
 
+    #[error("CRC-32 mismatch in {entry}: expected {expected:#010x}, got {actual:#010x}")]
+    CrcMismatch {
+        entry: String,
+        expected: u32,
+        actual: u32
+    },
+
+    #[error("{0} is encrypted, but no password was provided")]
+    PasswordRequired(String),
+
+    #[error("wrong password for {0}")]
+    WrongPassword(String),
+
+    #[error("encrypted data of {0} is shorter than the encryption header")]
+    EncryptedDataTooShort(String),
+
+    #[error("{0} is encrypted with an unsupported method")]
+    UnsupportedEncryption(String),
+
+    #[error("authentication code mismatch in {0}")]
+    AuthenticationFailed(String),
+
+    #[error("symlink target of {0} is longer than {1} bytes")]
+    SymlinkTargetTooLong(String, usize),
+
+    #[error("{limit:?} limit exceeded{}", entry.as_ref().map(|e| format!(" by {e}")).unwrap_or_default())]
+    LimitExceeded {
+        limit: ZipLimit,
+        entry: Option<String>
+    },
+
+    #[error("{entry} uses an unsupported feature: {feature:?}")]
+    UnsupportedFeature {
+        feature: UnsupportedFeature,
+        entry: String
+    },
+
+    #[error("local file header of {0} does not match the central directory: {1:?}")]
+    HeaderMismatch(String, Vec<HeaderField>),
+
     #[error("error within callback: {0}")]
     FromDecodeCallback(#[from] anyhow::Error)
 }
@@ -37,7 +88,20 @@ pub enum DecoderError {
 #[derive(Debug)]
 enum ZipDecoderState {
     FileHeader,
-    FileData(u64, LocalFileHeader, Option<Box<dyn Decompressor>>)
+    EncryptionHeader(LocalFileHeader),
+    FileData {
+        pos: u64,
+        end: u64,
+        lfh: LocalFileHeader,
+        decryptor: Option<Box<dyn Decryptor>>,
+        decrypted: Vec<u8>,
+        decompressor: Option<Box<dyn Decompressor>>,
+        hasher: Option<Hasher>,
+        output: u64,
+        symlink_target: Option<Vec<u8>>
+    },
+    EncryptionTrailer(LocalFileHeader, Box<dyn Decryptor>),
+    DataDescriptor(bool)
 }
 
 /// Represents a position in a (possbly multipart) ZIP archive
@@ -68,16 +132,53 @@ impl ZipPosition {
     }
 }
 
+/// Determines what happens if a local file header or a data descriptor does not match
+/// the corresponding central directory file header. See [LocalFileHeader::mismatches]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum HeaderMismatchPolicy {
+    /// Return [DecoderError::HeaderMismatch]
+    Strict,
+
+    /// Emit [ZipDecodedData::HeaderMismatch] and continue
+    Warn,
+
+    /// Don't compare the headers
+    #[default]
+    Lenient
+}
+
 /// A chunk of decoded ZIP data
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum ZipDecodedData<'a> {
     /// The ZIP file headers for a file
     FileHeader(&'a CentralDirectoryFileHeader, &'a LocalFileHeader),
 
+    /// The fields which differ between the headers of a file. Only emitted with
+    /// [HeaderMismatchPolicy::Warn], before [ZipDecodedData::FileHeader], or after
+    /// the file data if the data descriptor differs ([HeaderField::DataDescriptor])
+    HeaderMismatch(&'a CentralDirectoryFileHeader, &'a [HeaderField]),
+
     /// Decoded (uncompressed or decompressed) file bytes 
-    FileData(&'a [u8])
+    FileData(&'a [u8]),
+
+    /// The target of a symbolic link. Emitted once the whole target is decoded,
+    /// instead of [ZipDecodedData::FileData]
+    Symlink {
+        header: &'a CentralDirectoryFileHeader,
+
+        /// The target, decoded the same way as the filename
+        target: &'a str,
+
+        /// The original bytes of the target. Unix targets are arbitrary
+        /// bytes, so these should be preferred when creating the link
+        target_raw: &'a [u8]
+    }
 }
 
+pub const DEFAULT_MAX_SYMLINK_TARGET_LENGTH: usize = 4096;
+
 /// A stream unpacker for ZIP archives
 pub struct ZipUnpacker<'a> {
     decoder_state: ZipDecoderState,
@@ -87,8 +188,18 @@ pub struct ZipUnpacker<'a> {
     disk_sizes: Vec<usize>,
     central_directory: SortedCentralDirectory,
 
+    verify_crc: bool,
+    max_symlink_target_length: usize,
+    header_mismatch_policy: HeaderMismatchPolicy,
+
+    limits: ZipLimits,
+    total_output: u64,
+
     #[allow(clippy::type_complexity)]
-    on_decode: Option<Box<dyn Fn(ZipDecodedData) -> anyhow::Result<()> + 'a>>
+    on_decode: Option<Box<dyn Fn(ZipDecodedData) -> anyhow::Result<()> + 'a>>,
+
+    #[allow(clippy::type_complexity)]
+    password_provider: Option<Box<dyn Fn(&CentralDirectoryFileHeader) -> Option<Vec<u8>> + 'a>>
 }
 
 impl std::fmt::Debug for ZipUnpacker<'_> {
@@ -98,6 +209,11 @@ impl std::fmt::Debug for ZipUnpacker<'_> {
             .field("current_index", &self.current_index)
             .field("current_position", &self.current_position)
             .field("disk_sizes", &self.disk_sizes)
+            .field("verify_crc", &self.verify_crc)
+            .field("max_symlink_target_length", &self.max_symlink_target_length)
+            .field("header_mismatch_policy", &self.header_mismatch_policy)
+            .field("limits", &self.limits)
+            .field("total_output", &self.total_output)
             .finish()
     }
 }
@@ -117,7 +233,15 @@ impl<'a> ZipUnpacker<'a> {
             disk_sizes,
             central_directory,
 
-            on_decode: None
+            verify_crc: true,
+            max_symlink_target_length: DEFAULT_MAX_SYMLINK_TARGET_LENGTH,
+            header_mismatch_policy: HeaderMismatchPolicy::default(),
+
+            limits: ZipLimits::default(),
+            total_output: 0,
+
+            on_decode: None,
+            password_provider: None
         }
     }
 
@@ -141,7 +265,15 @@ impl<'a> ZipUnpacker<'a> {
             disk_sizes,
             central_directory,
 
-            on_decode: None
+            verify_crc: true,
+            max_symlink_target_length: DEFAULT_MAX_SYMLINK_TARGET_LENGTH,
+            header_mismatch_policy: HeaderMismatchPolicy::default(),
+
+            limits: ZipLimits::default(),
+            total_output: 0,
+
+            on_decode: None,
+            password_provider: None
         })
     }
 
@@ -151,6 +283,44 @@ impl<'a> ZipUnpacker<'a> {
         self.on_decode = Some(Box::new(on_decode));
     }
 
+    /// Sets the password provider. The passed closure will be invoked
+    /// for every encrypted file and must return its password, or None
+    /// if it is not known, which results in [DecoderError::PasswordRequired]
+    pub fn set_password_provider(&mut self, password_provider: impl Fn(&CentralDirectoryFileHeader) -> Option<Vec<u8>> + 'a) {
+        self.password_provider = Some(Box::new(password_provider));
+    }
+
+    /// Sets whether the CRC-32 of every decoded file should be verified.
+    /// Enabled by default. If enabled, [DecoderError::CrcMismatch] is
+    /// returned after the last chunk of a corrupted file is decoded
+    pub fn set_verify_crc(&mut self, verify_crc: bool) {
+        self.verify_crc = verify_crc;
+    }
+
+    /// Sets the maximum length of a symbolic link target in bytes. Targets are
+    /// buffered in memory, so longer ones result in [DecoderError::SymlinkTargetTooLong].
+    /// Defaults to [DEFAULT_MAX_SYMLINK_TARGET_LENGTH]
+    pub fn set_max_symlink_target_length(&mut self, max_symlink_target_length: usize) {
+        self.max_symlink_target_length = max_symlink_target_length;
+    }
+
+    /// Sets what happens if a local file header or a data descriptor does not match the
+    /// corresponding central directory file header. Defaults to [HeaderMismatchPolicy::Lenient]
+    pub fn set_header_mismatch_policy(&mut self, header_mismatch_policy: HeaderMismatchPolicy) {
+        self.header_mismatch_policy = header_mismatch_policy;
+    }
+
+    /// Sets the resource limits. The declared sizes of the remaining files are checked
+    /// immediately, and the amount of actually decoded bytes is checked while unpacking.
+    /// Returns [DecoderError::LimitExceeded] if a limit is exceeded
+    pub fn set_limits(&mut self, limits: ZipLimits) -> Result<(), DecoderError> {
+        let headers = self.central_directory.headers_ref();
+        limits.check_headers(&headers[self.current_index.min(headers.len())..])?;
+
+        self.limits = limits;
+        Ok(())
+    }
+
     /// Update this ZipUnpacker with new bytes. The callback may or
     /// may not be fired, depending on the content. The callback may
     /// be fired multiple times.
@@ -195,14 +365,14 @@ impl<'a> ZipUnpacker<'a> {
     }
 
     fn update_internal(&mut self, data: impl AsRef<[u8]>) -> Result<(usize, bool), DecoderError> {
+        let data = data.as_ref();
+
         let headers = self.central_directory.headers_ref();
         if self.current_index >= headers.len() {
             return Ok((0, true));
         }
         let cdfh = &headers[self.current_index];
 
-        let data = data.as_ref();
-
         match &mut self.decoder_state {
             ZipDecoderState::FileHeader => {
                 if self.current_position > cdfh.header_position() {
@@ -218,6 +388,13 @@ impl<'a> ZipUnpacker<'a> {
                     return Ok((std::cmp::min(cdfh.local_header_offset as usize - self.current_position.offset, data.len()), false));
                 }
 
+                if let Some(feature) = cdfh.unsupported_feature() {
+                    return Err(DecoderError::UnsupportedFeature {
+                        feature,
+                        entry: cdfh.filename.clone()
+                    });
+                }
+
                 if data.len() < 4 + LFH_CONSTANT_SIZE {
                     return Ok((0, false));
                 }
@@ -232,29 +409,80 @@ impl<'a> ZipUnpacker<'a> {
                 };
                 let header_size = lfh.header_size;
 
+                if let Some(feature) = lfh.unsupported_feature() {
+                    return Err(DecoderError::UnsupportedFeature {
+                        feature,
+                        entry: cdfh.filename.clone()
+                    });
+                }
+
+                if self.header_mismatch_policy != HeaderMismatchPolicy::Lenient {
+                    report_mismatches(self.header_mismatch_policy, self.on_decode.as_deref(), cdfh, lfh.mismatches(cdfh))?;
+                }
+
                 if let Some(on_decode) = &self.on_decode {
                     (on_decode)(ZipDecodedData::FileHeader(cdfh, &lfh))?;
                 }
 
-                if lfh.uncompressed_size != 0 {
-                    let decompressor = lfh.compression_method
-                        .as_ref()
-                        .map(|m| m.create_decompressor())
-                        .transpose()?;
-
-                    self.decoder_state = ZipDecoderState::FileData(0, lfh, decompressor);
+                let compressed_size = compressed_size(cdfh, &lfh);
+                if compressed_size != 0 {
+                    if lfh.is_encrypted() {
+                        self.decoder_state = ZipDecoderState::EncryptionHeader(lfh);
+                    } else {
+                        self.decoder_state = self.file_data_state(cdfh, lfh, 0, None)?;
+                    }
                 } else {
-                    self.decoder_state = ZipDecoderState::FileHeader;
-                    self.current_index += 1;
+                    // A symbolic link with an empty target has no data
+                    if let (EntryKind::Symlink, Some(on_decode)) = (cdfh.entry_kind(), &self.on_decode) {
+                        emit_symlink(on_decode, cdfh, &[])?;
+                    }
+
+                    self.finish_file(&lfh);
                 }
 
                 Ok((4 + header_size, false))
             },
 
-            ZipDecoderState::FileData(pos, lfh, decompressor) => {
-                let bytes_left = lfh.compressed_size - *pos;
+            ZipDecoderState::EncryptionHeader(lfh) => {
+                let header_size = encryption_header_size(cdfh, lfh)?;
+                if compressed_size(cdfh, lfh) < header_size as u64 {
+                    return Err(DecoderError::EncryptedDataTooShort(cdfh.filename.clone()));
+                }
+
+                if data.len() < header_size {
+                    return Ok((0, false));
+                }
+
+                let password = self.password_provider
+                    .as_ref()
+                    .and_then(|p| (p)(cdfh))
+                    .ok_or_else(|| DecoderError::PasswordRequired(cdfh.filename.clone()))?;
+
+                let decryptor = create_decryptor(cdfh, lfh, password, &data[..header_size])?;
+
+                let lfh = lfh.clone();
+                self.decoder_state = self.file_data_state(cdfh, lfh, header_size as u64, Some(decryptor))?;
+
+                Ok((header_size, false))
+            },
+
+            ZipDecoderState::FileData { pos, end, decryptor, decrypted, decompressor, hasher, output, symlink_target, .. } => {
+                let bytes_left = *end - *pos;
                 let bytes_to_read = std::cmp::min(bytes_left as usize, data.len());
-                let file_bytes = &data[..bytes_to_read];
+
+                let file_bytes = if let Some(decryptor) = decryptor {
+                    // Bytes that were decrypted, but not consumed by the decompressor
+                    // yet will be passed again, so they must not be decrypted twice
+                    let decrypted_count = decrypted.len();
+                    if bytes_to_read > decrypted_count {
+                        decrypted.extend_from_slice(&data[decrypted_count..bytes_to_read]);
+                        decryptor.decrypt(&mut decrypted[decrypted_count..]);
+                    }
+
+                    &decrypted[..bytes_to_read]
+                } else {
+                    &data[..bytes_to_read]
+                };
 
                 let (count, decompressed) = if let Some(decompressor) = decompressor {
                     decompressor.update(file_bytes)?
@@ -263,17 +491,364 @@ impl<'a> ZipUnpacker<'a> {
                 };
                 *pos += count as u64;
 
-                if let Some(on_decode) = &self.on_decode {
+                *output += decompressed.len() as u64;
+                self.total_output += decompressed.len() as u64;
+                self.limits.check_entry(cdfh, *end, *output)?;
+                self.limits.check_total(self.total_output)?;
+
+                if let Some(hasher) = hasher {
+                    hasher.update(decompressed);
+                }
+
+                if let Some(symlink_target) = symlink_target.as_mut() {
+                    symlink_target.extend_from_slice(decompressed);
+                    if symlink_target.len() > self.max_symlink_target_length {
+                        return Err(DecoderError::SymlinkTargetTooLong(cdfh.filename.clone(), self.max_symlink_target_length));
+                    }
+                } else if let Some(on_decode) = &self.on_decode {
                     (on_decode)(ZipDecodedData::FileData(decompressed))?;
                 }
 
+                if decryptor.is_some() {
+                    decrypted.drain(..count);
+                }
+
                 if count as u64 == bytes_left {
-                    self.decoder_state = ZipDecoderState::FileHeader;
-                    self.current_index += 1;
+                    if let Some(hasher) = hasher.take() {
+                        let actual = hasher.finalize();
+                        if actual != cdfh.crc32 {
+                            return Err(DecoderError::CrcMismatch {
+                                entry: cdfh.filename.clone(),
+                                expected: cdfh.crc32,
+                                actual
+                            });
+                        }
+                    }
+
+                    if let (Some(symlink_target), Some(on_decode)) = (symlink_target, &self.on_decode) {
+                        emit_symlink(on_decode, cdfh, symlink_target)?;
+                    }
+
+                    let ZipDecoderState::FileData { lfh, decryptor, .. } = std::mem::replace(&mut self.decoder_state, ZipDecoderState::FileHeader) else {
+                        unreachable!()
+                    };
+
+                    match decryptor {
+                        Some(decryptor) if decryptor.trailer_size() != 0 => {
+                            self.decoder_state = ZipDecoderState::EncryptionTrailer(lfh, decryptor);
+                        },
+
+                        _ => self.finish_file(&lfh)
+                    }
                 }
 
                 Ok((count, false))
+            },
+
+            ZipDecoderState::EncryptionTrailer(_, decryptor) => {
+                let trailer_size = decryptor.trailer_size();
+                if data.len() < trailer_size {
+                    return Ok((0, false));
+                }
+
+                if !decryptor.verify_trailer(&data[..trailer_size]) {
+                    return Err(DecoderError::AuthenticationFailed(cdfh.filename.clone()));
+                }
+
+                let ZipDecoderState::EncryptionTrailer(lfh, _) = std::mem::replace(&mut self.decoder_state, ZipDecoderState::FileHeader) else {
+                    unreachable!()
+                };
+                self.finish_file(&lfh);
+
+                Ok((trailer_size, false))
+            },
+
+            ZipDecoderState::DataDescriptor(zip64) => {
+                // Some writers use 8 byte sizes for large files without adding a ZIP64
+                // extra field to the LFH, so the CDFH is taken into account as well
+                let zip64 = *zip64
+                    || cdfh.is_zip64()
+                    || cdfh.compressed_size >= u32::MAX as u64
+                    || cdfh.uncompressed_size >= u32::MAX as u64;
+
+                let Some(descriptor) = DataDescriptor::from_bytes_matching(data, zip64, cdfh.crc32, cdfh.compressed_size, cdfh.uncompressed_size) else {
+                    return Ok((0, false));
+                };
+
+                if self.header_mismatch_policy != HeaderMismatchPolicy::Lenient && !descriptor.matches(cdfh.crc32, cdfh.compressed_size, cdfh.uncompressed_size) {
+                    report_mismatches(self.header_mismatch_policy, self.on_decode.as_deref(), cdfh, vec![HeaderField::DataDescriptor])?;
+                }
+
+                self.decoder_state = ZipDecoderState::FileHeader;
+                self.current_index += 1;
+
+                Ok((descriptor.descriptor_size, false))
+            }
+        }
+    }
+
+    fn file_data_state(&self, cdfh: &CentralDirectoryFileHeader, lfh: LocalFileHeader, pos: u64, decryptor: Option<Box<dyn Decryptor>>) -> Result<ZipDecoderState, DecoderError> {
+        // For AES encrypted files, the actual compression method
+        // is stored in the extra field, and the CRC-32 may be omitted
+        let (compression_method, has_crc32) = match lfh.aes_extra_field() {
+            Some(aes) if lfh.is_encrypted() => (CompressionMethod::from_id(aes.compression_method), aes.has_crc32()),
+            _ => (lfh.compression_method.clone(), true)
+        };
+
+        let decompressor = compression_method
+            .as_ref()
+            .map(|m| m.create_decompressor())
+            .transpose()?;
+
+        let hasher = (self.verify_crc && has_crc32).then(Hasher::new);
+
+        let trailer_size = decryptor.as_ref().map_or(0, |d| d.trailer_size());
+        let end = compressed_size(cdfh, &lfh).saturating_sub(trailer_size as u64).max(pos);
+
+        let symlink_target = (cdfh.entry_kind() == EntryKind::Symlink).then(Vec::new);
+
+        Ok(ZipDecoderState::FileData {
+            pos,
+            end,
+            lfh,
+            decryptor,
+            decrypted: Vec::new(),
+            decompressor,
+            hasher,
+            output: 0,
+            symlink_target
+        })
+    }
+
+    /// Moves on to the data descriptor of the current file if
+    /// it has one, or to the header of the next file otherwise
+    fn finish_file(&mut self, lfh: &LocalFileHeader) {
+        if lfh.has_data_descriptor() {
+            self.decoder_state = ZipDecoderState::DataDescriptor(lfh.is_zip64());
+        } else {
+            self.decoder_state = ZipDecoderState::FileHeader;
+            self.current_index += 1;
+        }
+    }
+}
+
+/// Returns [DecoderError::HeaderMismatch] or emits [ZipDecodedData::HeaderMismatch],
+/// depending on the policy, if there are any mismatched fields
+#[allow(clippy::type_complexity)]
+fn report_mismatches(policy: HeaderMismatchPolicy, on_decode: Option<&dyn Fn(ZipDecodedData) -> anyhow::Result<()>>, cdfh: &CentralDirectoryFileHeader, mismatches: Vec<HeaderField>) -> Result<(), DecoderError> {
+    if mismatches.is_empty() {
+        return Ok(());
+    }
+
+    if policy == HeaderMismatchPolicy::Strict {
+        return Err(DecoderError::HeaderMismatch(cdfh.filename.clone(), mismatches));
+    }
+
+    if let Some(on_decode) = on_decode {
+        (on_decode)(ZipDecodedData::HeaderMismatch(cdfh, &mismatches))?;
+    }
+
+    Ok(())
+}
+
+/// Emits [ZipDecodedData::Symlink] for a fully decoded target
+fn emit_symlink(on_decode: &dyn Fn(ZipDecodedData) -> anyhow::Result<()>, cdfh: &CentralDirectoryFileHeader, target: &[u8]) -> Result<(), DecoderError> {
+    let decoded = decode_header_text(target, cdfh.flag & FLAG_UTF8 != 0);
+    (on_decode)(ZipDecodedData::Symlink {
+        header: cdfh,
+        target: &decoded,
+        target_raw: target
+    })?;
+
+    Ok(())
+}
+
+/// Returns the size of the encryption header which precedes the encrypted data
+fn encryption_header_size(cdfh: &CentralDirectoryFileHeader, lfh: &LocalFileHeader) -> Result<usize, DecoderError> {
+    match lfh.aes_extra_field() {
+        #[cfg(feature = "zip-aes")]
+        Some(aes) => aes.salt_size()
+            .map(|s| s + AES_PASSWORD_VERIFIER_SIZE)
+            .ok_or_else(|| DecoderError::UnsupportedEncryption(cdfh.filename.clone())),
+
+        #[cfg(not(feature = "zip-aes"))]
+        Some(_) => Err(DecoderError::UnsupportedEncryption(cdfh.filename.clone())),
+
+        None => Ok(ZIPCRYPTO_HEADER_SIZE)
+    }
+}
+
+/// Creates a [Decryptor] for a file from its encryption header
+fn create_decryptor(cdfh: &CentralDirectoryFileHeader, lfh: &LocalFileHeader, password: Vec<u8>, header: &[u8]) -> Result<Box<dyn Decryptor>, DecoderError> {
+    #[cfg(feature = "zip-aes")]
+    if let Some(key_size) = lfh.aes_extra_field().and_then(|a| a.key_size()) {
+        let decryptor = AesDecryptor::from_header(password, key_size, header)
+            .ok_or_else(|| DecoderError::WrongPassword(cdfh.filename.clone()))?;
+
+        return Ok(Box::new(decryptor));
+    }
+
+    let check_byte = if lfh.has_data_descriptor() {
+        (lfh.mod_time >> 8) as u8
+    } else {
+        (cdfh.crc32 >> 24) as u8
+    };
+
+    let decryptor = ZipCryptoDecryptor::from_header(password, header.try_into().unwrap(), check_byte)
+        .ok_or_else(|| DecoderError::WrongPassword(cdfh.filename.clone()))?;
+
+    Ok(Box::new(decryptor))
+}
+
+/// Returns the compressed size of a file. If the file has a data descriptor,
+/// the sizes in the LFH are zero, so the one from the CDFH is used instead
+fn compressed_size(cdfh: &CentralDirectoryFileHeader, lfh: &LocalFileHeader) -> u64 {
+    if lfh.has_data_descriptor() {
+        cdfh.compressed_size
+    } else {
+        lfh.compressed_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::{ZipUnpacker, ZipDecodedData, read_cd};
+
+    /// Created with Info-ZIP ("zip -P secret"). Contains a deflated
+    /// and a stored file, both followed by data descriptors
+    const ZIPCRYPTO_ARCHIVE: [u8; 1029] = [
+        0x50, 0x4b, 0x03, 0x04, 0x14, 0x00, 0x09, 0x00, 0x08, 0x00, 0xc5, 0xbb, 0x50, 0x5d, 0xba, 0xc5,
+        0x35, 0x84, 0x01, 0x03, 0x00, 0x00, 0x88, 0xa3, 0x01, 0x00, 0x07, 0x00, 0x00, 0x00, 0x62, 0x69,
+        0x67, 0x2e, 0x74, 0x78, 0x74, 0x8d, 0x41, 0xb4, 0x19, 0x81, 0xa5, 0xc3, 0x22, 0xae, 0x3f, 0x55,
+        0x35, 0x5b, 0x7e, 0x20, 0x10, 0xce, 0x6d, 0xbe, 0xc9, 0x32, 0x34, 0xea, 0xaf, 0xd1, 0xc1, 0x12,
+        0x02, 0x96, 0x95, 0x5d, 0x80, 0xc9, 0xdd, 0x04, 0x29, 0x84, 0x2a, 0x39, 0x80, 0x61, 0x10, 0x83,
+        0xa9, 0x7a, 0x9b, 0xe9, 0x76, 0x22, 0x14, 0xc6, 0xde, 0xc9, 0xd8, 0xa7, 0x2e, 0xf7, 0xe4, 0xdc,
+        0xf3, 0x18, 0x12, 0xc5, 0x69, 0xb8, 0xf6, 0xe8, 0x25, 0x6c, 0xa8, 0xea, 0x7e, 0x39, 0xc1, 0x11,
+        0xbf, 0x9a, 0x1a, 0x43, 0x38, 0x8e, 0x79, 0x15, 0xee, 0x2d, 0xbf, 0x26, 0xc8, 0xcf, 0x51, 0xd6,
+        0x39, 0x17, 0x04, 0x18, 0x7c, 0x16, 0xf7, 0x86, 0xfe, 0x20, 0x61, 0x3d, 0xef, 0xe7, 0xc3, 0xe6,
+        0x18, 0xcd, 0x7c, 0xdc, 0x3b, 0x4e, 0xf8, 0x1b, 0x07, 0x86, 0x09, 0x3d, 0xcd, 0xc8, 0x53, 0xae,
+        0xd2, 0x9b, 0xc7, 0x44, 0x5c, 0xc0, 0xb2, 0x6c, 0xd0, 0xc9, 0xa3, 0xad, 0x85, 0x41, 0xc8, 0xc4,
+        0x04, 0x37, 0x44, 0xf1, 0x6d, 0xcb, 0x26, 0xec, 0x6b, 0x21, 0xfd, 0x61, 0x41, 0x3c, 0x72, 0xb9,
+        0xb8, 0x1c, 0xbe, 0x3b, 0x5e, 0xfe, 0x3c, 0x78, 0x95, 0x43, 0xc9, 0xc9, 0xef, 0x96, 0x88, 0x71,
+        0xf3, 0x5b, 0x7c, 0xca, 0x28, 0x68, 0x8d, 0x6a, 0x3c, 0x60, 0x29, 0x61, 0xd0, 0x77, 0x35, 0xea,
+        0x5d, 0x36, 0x3a, 0x5d, 0xad, 0x05, 0x21, 0xb7, 0xd1, 0xa1, 0x95, 0x1e, 0x86, 0x84, 0x55, 0xf0,
+        0xee, 0xb1, 0x0c, 0x97, 0x71, 0x96, 0x3d, 0x8e, 0x27, 0x0c, 0x30, 0x96, 0x8a, 0x71, 0x49, 0x98,
+        0x4e, 0xc9, 0xcc, 0x12, 0xa2, 0x95, 0xd0, 0x0c, 0xa1, 0x8d, 0x87, 0x65, 0x6a, 0xaa, 0xc5, 0x91,
+        0x6a, 0x2b, 0xe1, 0x7e, 0x68, 0xbb, 0x89, 0x35, 0x71, 0x9b, 0x77, 0xba, 0x65, 0x66, 0x2b, 0xc6,
+        0x2f, 0x3b, 0x0d, 0x30, 0x63, 0x85, 0x1c, 0x2b, 0xe0, 0xe8, 0x70, 0xe3, 0xb9, 0x18, 0x00, 0xff,
+        0x7c, 0x4d, 0xcf, 0x95, 0x08, 0x99, 0x48, 0x43, 0xdc, 0x29, 0xe6, 0x4a, 0x94, 0x27, 0x77, 0x0e,
+        0x42, 0xa2, 0xd3, 0xfa, 0x88, 0x6d, 0x2c, 0x57, 0x0f, 0x6a, 0x29, 0xa1, 0x78, 0xc8, 0x4b, 0x2e,
+        0x6d, 0x17, 0x41, 0xdb, 0x23, 0xc9, 0x8c, 0x2a, 0xf1, 0x6d, 0xc9, 0xf9, 0x33, 0x16, 0xb7, 0x7c,
+        0x7d, 0x2b, 0xcd, 0x5e, 0xa8, 0x78, 0x02, 0xb7, 0x1c, 0x09, 0x2f, 0xbf, 0x14, 0x5e, 0x56, 0xd2,
+        0x6e, 0xa2, 0x12, 0x4b, 0xa4, 0x34, 0x71, 0x7d, 0xfc, 0x91, 0xa4, 0xea, 0x02, 0xb8, 0x0b, 0x8f,
+        0x86, 0xd8, 0xce, 0x71, 0x0a, 0xe6, 0x76, 0x29, 0xba, 0x0b, 0xc8, 0xf2, 0x12, 0x75, 0xb7, 0x3e,
+        0x77, 0x23, 0xd4, 0x66, 0x28, 0x33, 0xeb, 0x16, 0x5c, 0x8d, 0xe3, 0x56, 0xd2, 0x95, 0x2c, 0x8b,
+        0xb2, 0x34, 0x5a, 0x74, 0xe7, 0xb6, 0xc8, 0x7f, 0x6e, 0x6d, 0x04, 0xa0, 0x98, 0xe9, 0x73, 0xb4,
+        0x28, 0xcd, 0xc5, 0xec, 0xa6, 0x61, 0xf4, 0x73, 0xa0, 0x78, 0xde, 0xa4, 0x17, 0xb0, 0x7e, 0x37,
+        0x6d, 0x3a, 0x21, 0x38, 0x33, 0xc2, 0x73, 0xa3, 0x48, 0xa7, 0x6b, 0x95, 0x54, 0x63, 0x06, 0xcb,
+        0xd5, 0x61, 0xf9, 0x43, 0xf1, 0x2b, 0xe8, 0x0e, 0xeb, 0x80, 0xdc, 0x9c, 0x9e, 0x5d, 0x0d, 0x1f,
+        0x1e, 0x02, 0xf5, 0x10, 0x46, 0x2e, 0x14, 0xcf, 0x66, 0x8a, 0xec, 0xd2, 0x03, 0x18, 0x86, 0x9e,
+        0x5c, 0xd6, 0xca, 0x52, 0x6b, 0x59, 0x3a, 0x79, 0x6d, 0x95, 0x14, 0xf1, 0x53, 0x94, 0x4f, 0xad,
+        0xc1, 0x64, 0xd6, 0xc6, 0xba, 0xc0, 0xf0, 0xeb, 0xa9, 0x44, 0xe4, 0x81, 0x41, 0xd7, 0x73, 0x84,
+        0x92, 0x33, 0xef, 0x0b, 0x54, 0xba, 0x95, 0x33, 0xb5, 0x46, 0x42, 0x69, 0x80, 0x57, 0x2e, 0xf5,
+        0x7e, 0x58, 0x5a, 0x7b, 0xcd, 0xe4, 0xe6, 0x59, 0x2b, 0x88, 0xbe, 0xe4, 0xda, 0xe6, 0x58, 0xbc,
+        0x56, 0xd2, 0xf0, 0x2b, 0x14, 0x85, 0xb1, 0x51, 0x5d, 0x55, 0xd9, 0xf3, 0x5a, 0x6d, 0x51, 0x5c,
+        0x57, 0x74, 0x97, 0x7e, 0x9c, 0xdd, 0x2e, 0xf5, 0xdb, 0x68, 0xad, 0xfe, 0x4b, 0xff, 0x2d, 0x15,
+        0x9a, 0x2a, 0x2c, 0x3b, 0xe0, 0x79, 0xdb, 0x0d, 0x03, 0x74, 0xc0, 0xfd, 0x4e, 0x0a, 0xd2, 0x8c,
+        0x34, 0xae, 0x63, 0x09, 0xed, 0xb8, 0x26, 0xf8, 0xc3, 0xe4, 0x5e, 0xdc, 0x59, 0x55, 0xd4, 0xeb,
+        0x0b, 0x8f, 0x00, 0x55, 0xb6, 0xca, 0xd8, 0x8b, 0x9c, 0x29, 0x32, 0x7b, 0xea, 0x4d, 0xff, 0x48,
+        0xcd, 0x00, 0xad, 0x61, 0xa3, 0x6a, 0x8b, 0x21, 0xbe, 0x2d, 0xea, 0x34, 0xaf, 0x17, 0x5c, 0x88,
+        0xc3, 0x0f, 0xa6, 0xba, 0x6d, 0xbd, 0x4c, 0xcf, 0x56, 0x42, 0x55, 0x77, 0x73, 0x9f, 0x46, 0x46,
+        0x1b, 0xe3, 0x79, 0x70, 0x50, 0xb0, 0xa0, 0x35, 0x04, 0x0b, 0x37, 0xba, 0xe6, 0xfb, 0x66, 0xc3,
+        0x14, 0x95, 0x50, 0xbf, 0xa2, 0x2c, 0x3e, 0x20, 0x81, 0x33, 0x7f, 0x9f, 0xc7, 0x77, 0x82, 0x4e,
+        0x69, 0xde, 0xf9, 0x32, 0x2a, 0x64, 0xfb, 0xb6, 0xaf, 0x88, 0x24, 0xf5, 0x33, 0x3a, 0x49, 0x44,
+        0x18, 0xac, 0xde, 0x15, 0xb2, 0x0f, 0x59, 0x43, 0xec, 0xde, 0x50, 0x2b, 0x41, 0x5c, 0x48, 0xa8,
+        0xea, 0x1f, 0xfa, 0xb2, 0x7c, 0x2a, 0xa7, 0xa4, 0xd8, 0x80, 0x72, 0xf5, 0xea, 0xad, 0x28, 0xc7,
+        0xfa, 0x36, 0x50, 0x7e, 0x2e, 0x74, 0x14, 0xc3, 0x0b, 0x3c, 0xd4, 0x33, 0xab, 0x02, 0x58, 0x8d,
+        0x56, 0xb8, 0x5f, 0xbc, 0x87, 0xcc, 0x1d, 0x16, 0x9f, 0x38, 0xc4, 0x3e, 0xc0, 0x0e, 0x4d, 0xc4,
+        0xf2, 0xec, 0x11, 0x9f, 0x28, 0x86, 0xb5, 0x4a, 0x5a, 0xa9, 0x8f, 0x5a, 0x1b, 0x36, 0x96, 0x71,
+        0x0d, 0x27, 0x37, 0x41, 0x4d, 0x16, 0x50, 0x4b, 0x07, 0x08, 0xba, 0xc5, 0x35, 0x84, 0x01, 0x03,
+        0x00, 0x00, 0x88, 0xa3, 0x01, 0x00, 0x50, 0x4b, 0x03, 0x04, 0x0a, 0x00, 0x09, 0x00, 0x00, 0x00,
+        0xac, 0xbb, 0x50, 0x5d, 0x31, 0x51, 0x6b, 0x17, 0x1e, 0x00, 0x00, 0x00, 0x12, 0x00, 0x00, 0x00,
+        0x05, 0x00, 0x00, 0x00, 0x62, 0x2e, 0x74, 0x78, 0x74, 0x75, 0xf7, 0xbf, 0xda, 0xbb, 0x91, 0x40,
+        0x12, 0x73, 0x99, 0xed, 0x45, 0x0c, 0x95, 0x77, 0x65, 0x7a, 0x94, 0x94, 0x42, 0x4e, 0xcd, 0x08,
+        0x90, 0x36, 0x7b, 0x87, 0x7a, 0xa8, 0xfd, 0x50, 0x4b, 0x07, 0x08, 0x31, 0x51, 0x6b, 0x17, 0x1e,
+        0x00, 0x00, 0x00, 0x12, 0x00, 0x00, 0x00, 0x50, 0x4b, 0x01, 0x02, 0x1e, 0x03, 0x14, 0x00, 0x09,
+        0x00, 0x08, 0x00, 0xc5, 0xbb, 0x50, 0x5d, 0xba, 0xc5, 0x35, 0x84, 0x01, 0x03, 0x00, 0x00, 0x88,
+        0xa3, 0x01, 0x00, 0x07, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0xa4,
+        0x81, 0x00, 0x00, 0x00, 0x00, 0x62, 0x69, 0x67, 0x2e, 0x74, 0x78, 0x74, 0x50, 0x4b, 0x01, 0x02,
+        0x1e, 0x03, 0x0a, 0x00, 0x09, 0x00, 0x00, 0x00, 0xac, 0xbb, 0x50, 0x5d, 0x31, 0x51, 0x6b, 0x17,
+        0x1e, 0x00, 0x00, 0x00, 0x12, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x01, 0x00, 0x00, 0x00, 0xa4, 0x81, 0x36, 0x03, 0x00, 0x00, 0x62, 0x2e, 0x74, 0x78, 0x74, 0x50,
+        0x4b, 0x05, 0x06, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x02, 0x00, 0x68, 0x00, 0x00, 0x00, 0x87,
+        0x03, 0x00, 0x00, 0x00, 0x00
+    ];
+
+    /// Unpacks the archive, making only "chunk_size" new bytes available at a time.
+    /// Bytes which were not consumed yet are passed again with the next chunk
+    fn unpack_in_chunks(archive: &[u8], chunk_size: usize) -> Vec<(String, Vec<u8>)> {
+        let central_directory = read_cd::from_provider(
+            vec![archive.len()],
+            false,
+            |pos, length| Ok(archive[pos.offset..(pos.offset + length)].to_vec())
+        ).unwrap().sort();
+
+        let files = RefCell::new(Vec::new());
+
+        let mut unpacker = ZipUnpacker::new(central_directory, vec![archive.len()]);
+        unpacker.set_password_provider(|_| Some(b"secret".to_vec()));
+        unpacker.set_callback(|data| {
+            match data {
+                ZipDecodedData::FileHeader(cdfh, _) => files.borrow_mut().push((cdfh.filename.clone(), Vec::new())),
+                ZipDecodedData::FileData(data) => files.borrow_mut().last_mut().unwrap().1.extend_from_slice(data),
+                _ => {}
+            }
+
+            Ok(())
+        });
+
+        let mut buffer = Vec::new();
+        let mut reached_end = false;
+        for chunk in archive.chunks(chunk_size) {
+            buffer.extend_from_slice(chunk);
+
+            let (advanced, end) = unpacker.update(&buffer).unwrap();
+            buffer.drain(..advanced);
+
+            if end {
+                reached_end = true;
+                break;
             }
         }
+
+        assert!(reached_end);
+        drop(unpacker);
+
+        files.into_inner()
+    }
+
+    fn expected_files() -> Vec<(String, Vec<u8>)> {
+        let big = (0..3000).map(|i| format!("line {} of a compressible text file\n", i % 50)).collect::<String>();
+
+        vec![
+            ("big.txt".to_owned(), big.into_bytes()),
+            ("b.txt".to_owned(), b"short second file\n".to_vec())
+        ]
+    }
+
+    #[test]
+    fn decodes_encrypted_archive_one_byte_at_a_time() {
+        assert_eq!(unpack_in_chunks(&ZIPCRYPTO_ARCHIVE, 1), expected_files());
+    }
+
+    #[test]
+    fn decodes_encrypted_archive_in_chunks() {
+        // The decompressor doesn't consume all of the input with these sizes, so
+        // decrypted bytes are carried over and must not be decrypted again
+        for chunk_size in [16, 400, ZIPCRYPTO_ARCHIVE.len()] {
+            assert_eq!(unpack_in_chunks(&ZIPCRYPTO_ARCHIVE, chunk_size), expected_files());
+        }
     }
 }
@@ -1,8 +1,9 @@
 use thiserror::Error;
 
-use super::{structures::{central_directory::{CentralDirectoryError, CentralDirectory}, cd_location::{CDLD_MAX_SIZE, EndOfCentralDirectory32, CentralDirectoryLocationData, EOCD64_LOCATOR_CONSTANT_SIZE, EOCD64_LOCATOR_SIGNATURE, EndOfCentralDirectory64Locator, EOCD64_CONSTANT_SIZE, EOCD64_SIGNATURE, EndOfCentralDirectory64}}, ZipPosition};
+use super::{structures::{central_directory::{CentralDirectoryError, CentralDirectory, CDFH_SIGNATURE}, local_file_header::{LFH_SIGNATURE, SPANNING_MARKER_SIZE, starts_with_spanning_marker}, file_header::UnsupportedFeature, cd_location::{CDLD_MAX_SIZE, EndOfCentralDirectory32, CentralDirectoryLocationData, EOCD64_LOCATOR_CONSTANT_SIZE, EOCD64_LOCATOR_SIGNATURE, EndOfCentralDirectory64Locator, EOCD64_CONSTANT_SIZE, EOCD64_SIGNATURE, EndOfCentralDirectory64, Eocd64ExtensibleData}}, ZipPosition};
 
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum CentralDirectoryReadError {
     #[error("failed to map required data spans to disks")]
     Map,
@@ -30,6 +31,68 @@ pub enum CentralDirectoryReadError {
 
     #[error("failed to decode central directory: {0}")]
     DecodeCentralDirectory(#[from] CentralDirectoryError),
+
+    #[error("unsupported feature{}: {feature:?}", entry.as_ref().map(|e| format!(" in {e}")).unwrap_or_default())]
+    UnsupportedFeature {
+        feature: UnsupportedFeature,
+        entry: Option<String>
+    },
+
+    #[error("central directory contains {actual} entries, but {expected} were declared")]
+    EntryCountMismatch {
+        expected: u64,
+        actual: usize
+    },
+
+    #[error("{entry} is located on disk {disk_number}, but the archive only has {disk_count} disks")]
+    DiskNumberOutOfRange {
+        entry: String,
+        disk_number: u32,
+        disk_count: u32
+    },
+}
+
+/// Archive-level information, read together with the central directory
+#[derive(Debug, Clone)]
+pub struct ArchiveInfo {
+    pub eocd32: EndOfCentralDirectory32,
+    pub eocd64_locator: Option<EndOfCentralDirectory64Locator>,
+    pub eocd64: Option<EndOfCentralDirectory64>,
+
+    /// The archive comment, decoded as CP437
+    #[cfg(feature = "zip-comments")]
+    pub comment: String,
+
+    /// The original bytes of the archive comment, before any decoding
+    #[cfg(feature = "zip-comments")]
+    pub comment_raw: Vec<u8>,
+
+    /// The actual position of the central directory, including the prepended data
+    pub cd_position: ZipPosition,
+    pub cd_size: u64,
+
+    /// The amount of disks declared by the end of central directory records
+    pub disk_count: u32,
+
+    /// The length of data prepended to the archive (for example, a self-extracting
+    /// stub or a spanning marker which is not accounted for). The offsets of the
+    /// files on the first disk are already adjusted by it
+    pub prefix_length: u64,
+
+    /// Whether the first disk starts with a spanning marker
+    pub has_spanning_marker: bool
+}
+
+impl ArchiveInfo {
+    /// Returns whether the archive has zip64 end of central directory records
+    pub fn is_zip64(&self) -> bool {
+        self.eocd64.is_some()
+    }
+
+    /// Returns the EOCD64 extensible data sector, if the archive has one
+    pub fn eocd64_extensible_data(&self) -> Option<&Eocd64ExtensibleData> {
+        self.eocd64.as_ref().and_then(|e| e.extensible_data.as_ref())
+    }
 }
 
 /// Tries to locate and read a central directory by using the provider callback.
@@ -42,61 +105,370 @@ pub enum CentralDirectoryReadError {
 /// 
 /// The arguments to the provider callback are a [ZipPosition] and length. It is guaranteed
 /// that the length will not exceed the remaining size of the disk
+/// 
+/// If the archive is not split (or is a cut one) and has data prepended to it (for example,
+/// a self-extracting archive), the offsets of all files are adjusted automatically
 pub fn from_provider(disk_sizes: impl AsRef<[usize]>, is_cut: bool, provider: impl Fn(ZipPosition, usize) -> Result<Vec<u8>, anyhow::Error>) -> Result<CentralDirectory, CentralDirectoryReadError> {
+    from_provider_with_info(disk_sizes, is_cut, provider).map(|(central_directory, _)| central_directory)
+}
+
+/// Same as [from_provider], but also returns the archive-level
+/// information, such as the comment and the zip64 records
+pub fn from_provider_with_info(disk_sizes: impl AsRef<[usize]>, is_cut: bool, provider: impl Fn(ZipPosition, usize) -> Result<Vec<u8>, anyhow::Error>) -> Result<(CentralDirectory, ArchiveInfo), CentralDirectoryReadError> {
     let disk_sizes = disk_sizes.as_ref();
     let total_size = disk_sizes.iter().sum::<usize>();
 
     let cdld_offset = total_size - std::cmp::min(total_size, CDLD_MAX_SIZE);
-    let cdld_bytes = make_calls(map_global_to_calls(disk_sizes, cdld_offset, CDLD_MAX_SIZE)?, &provider)?;
+    let cdld_bytes = make_calls(map_global_to_calls(disk_sizes, cdld_offset, total_size - cdld_offset)?, &provider)?;
+
+    let candidates = EndOfCentralDirectory32::find_offsets(&cdld_bytes);
+    if candidates.is_empty() {
+        return Err(CentralDirectoryReadError::NoEOCD32);
+    }
+
+    // The signature may appear inside the comment or trailing data, so every candidate
+    // is checked. Candidates which end exactly at the end of the data are tried first
+    let mut candidates = candidates.into_iter()
+        .filter_map(|offset| {
+            let eocd32 = EndOfCentralDirectory32::from_bytes(&cdld_bytes[(offset + 4)..])?;
+            let is_exact = offset + 4 + eocd32.eocd32_size == cdld_bytes.len();
+            Some((offset, eocd32, is_exact))
+        })
+        .collect::<Vec<_>>();
+    candidates.sort_by_key(|(_, _, is_exact)| !is_exact);
+
+    if candidates.is_empty() {
+        return Err(CentralDirectoryReadError::BadEOCD32);
+    }
+
+    let any_exact = candidates[0].2;
+
+    let mut first_error = None;
+    let mut located = None;
+    for (eocd32_offset, eocd32, _) in candidates {
+        match locate_central_directory(disk_sizes, is_cut, &provider, &cdld_bytes, cdld_offset, eocd32_offset, eocd32) {
+            Ok(l) => {
+                located = Some(l);
+                break;
+            },
+
+            Err(e) => {
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+
+    let Some(located) = located else {
+        // If no candidate ends at the end of the data, the disk sizes are probably wrong
+        return Err(if any_exact {
+            first_error.unwrap()
+        } else {
+            CentralDirectoryReadError::InvalidDiskSizes
+        });
+    };
+
+    let cd_pos = ZipPosition::new(
+        located.cdld.cd_disk_number as usize,
+        (located.cdld.cd_offset + located.shift) as usize
+    );
+    let cd_bytes = make_calls(map_to_calls(disk_sizes, cd_pos, located.cdld.cd_size as usize, is_cut)?, &provider)?;
+
+    let mut central_directory = CentralDirectory::from_bytes(cd_bytes)?;
+    located.validate(&central_directory)?;
+
+    let has_spanning_marker = has_spanning_marker(disk_sizes, is_cut, &provider)?;
+
+    // If the central directory is on another disk, a spanning marker which the offsets on
+    // the first disk don't account for can only be detected by looking at the first LFH
+    let mut shift = located.shift;
+    if has_spanning_marker && shift == 0 {
+        let first_offset = central_directory.headers_ref().iter()
+            .filter(|h| h.disk_number == 0)
+            .map(|h| h.local_header_offset)
+            .min();
+
+        if let Some(offset) = first_offset.and_then(|o| usize::try_from(o).ok()) {
+            let marker_pos = offset.checked_add(SPANNING_MARKER_SIZE).map(|o| ZipPosition::new(0, o));
+            if let Some(marker_pos) = marker_pos {
+                if !has_signature(disk_sizes, ZipPosition::new(0, offset), LFH_SIGNATURE, is_cut, &provider)? && has_signature(disk_sizes, marker_pos, LFH_SIGNATURE, is_cut, &provider)? {
+                    shift = SPANNING_MARKER_SIZE as u64;
+                }
+            }
+        }
+    }
+
+    // Prepended data can only be detected in front of the first disk
+    central_directory.shift_disk_offsets(0, shift)?;
+
+    let info = ArchiveInfo {
+        disk_count: located.disk_count(),
 
-    let eocd32_offset = EndOfCentralDirectory32::find_offset(&cdld_bytes)
-        .ok_or(CentralDirectoryReadError::NoEOCD32)?;
+        #[cfg(feature = "zip-comments")]
+        comment: located.cdld.comment,
 
-    let eocd32 = EndOfCentralDirectory32::from_bytes(&cdld_bytes[(eocd32_offset + 4)..])
-        .ok_or(CentralDirectoryReadError::BadEOCD32)?;
+        #[cfg(feature = "zip-comments")]
+        comment_raw: located.cdld.comment_raw,
 
-    if eocd32_offset + 4 + eocd32.eocd32_size as usize != cdld_bytes.len() {
-        return Err(CentralDirectoryReadError::InvalidDiskSizes);
+        cd_position: cd_pos,
+        cd_size: located.cdld.cd_size,
+        prefix_length: shift,
+        has_spanning_marker,
+
+        eocd32: located.eocd32,
+        eocd64_locator: located.eocd64_locator,
+        eocd64: located.eocd64
+    };
+
+    Ok((central_directory, info))
+}
+
+/// The end of central directory records which
+/// were used to locate the central directory
+struct LocatedCentralDirectory {
+    eocd32: EndOfCentralDirectory32,
+    eocd64_locator: Option<EndOfCentralDirectory64Locator>,
+    eocd64: Option<EndOfCentralDirectory64>,
+    cdld: CentralDirectoryLocationData,
+
+    /// The length of data prepended to the archive
+    shift: u64
+}
+
+impl LocatedCentralDirectory {
+    /// Checks that the central directory contains the declared amount of
+    /// entries, that they are on existing disks and don't use unsupported features
+    fn validate(&self, central_directory: &CentralDirectory) -> Result<(), CentralDirectoryReadError> {
+        let headers = central_directory.headers_ref();
+
+        // The EOCD64 count is authoritative whenever it is present. Without it, some
+        // writers store the entry count modulo 2^16 instead of using zip64
+        let (count_matches, expected) = match &self.eocd64 {
+            Some(eocd64) => (eocd64.cd_entry_count_total == headers.len() as u64, eocd64.cd_entry_count_total),
+            None => (self.eocd32.cd_entry_count_total == headers.len() as u16, self.eocd32.cd_entry_count_total as u64)
+        };
+
+        if !count_matches {
+            return Err(CentralDirectoryReadError::EntryCountMismatch {
+                expected,
+                actual: headers.len()
+            });
+        }
+
+        for cdfh in headers {
+            if let Some(feature) = cdfh.unsupported_feature() {
+                return Err(CentralDirectoryReadError::UnsupportedFeature {
+                    feature,
+                    entry: Some(cdfh.filename.clone())
+                });
+            }
+        }
+
+        let disk_count = self.disk_count();
+        if let Some(cdfh) = headers.iter().find(|h| h.disk_number >= disk_count) {
+            return Err(CentralDirectoryReadError::DiskNumberOutOfRange {
+                entry: cdfh.filename.clone(),
+                disk_number: cdfh.disk_number,
+                disk_count
+            });
+        }
+
+        Ok(())
     }
 
-    let cdld = if !eocd32.requires_zip64() || eocd32_offset < 4 + EOCD64_LOCATOR_CONSTANT_SIZE {
-        CentralDirectoryLocationData::from_eocd32(eocd32)
+    /// Returns the amount of disks declared by the end of central directory records
+    fn disk_count(&self) -> u32 {
+        match (&self.eocd64_locator, &self.eocd64) {
+            (Some(locator), _) if locator.disk_count != 0 => locator.disk_count,
+            (_, Some(eocd64)) => eocd64.disk_number.saturating_add(1),
+            _ => self.eocd32.disk_number as u32 + 1
+        }
+    }
+}
+
+/// Reads the records which belong to an EOCD32 candidate and checks
+/// that the central directory they point to starts with a CDFH
+fn locate_central_directory(
+    disk_sizes: &[usize],
+    is_cut: bool,
+    provider: &dyn Fn(ZipPosition, usize) -> Result<Vec<u8>, anyhow::Error>,
+    cdld_bytes: &[u8],
+    cdld_offset: usize,
+    eocd32_offset: usize,
+    eocd32: EndOfCentralDirectory32
+) -> Result<LocatedCentralDirectory, CentralDirectoryReadError> {
+    // Prepended data can only be detected if global offsets are known
+    let is_single = disk_sizes.len() == 1 || is_cut;
+
+    let mut eocd64 = None;
+    let mut eocd64_locator = None;
+
+    // Zip64 records may be present even if they are not required
+    let has_locator = eocd32_offset >= 4 + EOCD64_LOCATOR_CONSTANT_SIZE && {
+        let locator_offset = eocd32_offset - 4 - EOCD64_LOCATOR_CONSTANT_SIZE;
+        u32::from_le_bytes(cdld_bytes[locator_offset..(locator_offset + 4)].try_into().unwrap()) == EOCD64_LOCATOR_SIGNATURE
+    };
+
+    if eocd32.requires_zip64() && eocd32_offset >= 4 + EOCD64_LOCATOR_CONSTANT_SIZE && !has_locator {
+        return Err(CentralDirectoryReadError::BadEOCD64Locator);
+    }
+
+    // Global offset of where the central directory actually ends
+    let (cdld, cd_end) = if !has_locator {
+        (CentralDirectoryLocationData::from_eocd32(eocd32.clone()), cdld_offset + eocd32_offset)
     } else {
         let locator_offset = eocd32_offset - 4 - EOCD64_LOCATOR_CONSTANT_SIZE;
-        
-        let locator_signature = u32::from_le_bytes(cdld_bytes[locator_offset..(locator_offset + 4)].try_into().unwrap());
-        if locator_signature != EOCD64_LOCATOR_SIGNATURE {
-            return Err(CentralDirectoryReadError::BadEOCD64Locator);
-        }
 
         let locator = EndOfCentralDirectory64Locator::from_bytes(&cdld_bytes[(locator_offset + 4)..])
             .ok_or(CentralDirectoryReadError::BadEOCD64Locator)?;
 
-        let eocd64_pos = ZipPosition::new(
+        let mut eocd64_pos = ZipPosition::new(
             locator.eocd64_disk_number as usize, 
             locator.eocd64_offset as usize
         );
+        let mut found_eocd64 = read_eocd64(disk_sizes, eocd64_pos, is_cut, provider)?;
+
+        // If there is prepended data, the EOCD64 is not where the locator says it is.
+        // It must end right before the locator, so look for it in the data that was read
+        let locator_global_offset = cdld_offset + locator_offset;
+        if found_eocd64.is_none() && is_single {
+            if let Some(offset) = find_eocd64_before(cdld_bytes, locator_offset) {
+                eocd64_pos = ZipPosition::from_offset(cdld_offset + offset);
+                found_eocd64 = read_eocd64(disk_sizes, eocd64_pos, is_cut, provider)?;
+            }
+        }
 
-        let eocd64_bytes = make_calls(map_to_calls(disk_sizes, eocd64_pos, 4 + EOCD64_CONSTANT_SIZE, is_cut)?, &provider)?;
+        let mut found_eocd64 = found_eocd64.ok_or(CentralDirectoryReadError::BadEOCD64)?;
+
+        let extensible_data_size = found_eocd64.extensible_data_size();
+        if extensible_data_size != 0 {
+            // The extensible data sector must end before the locator
+            let eocd64_length = usize::try_from(extensible_data_size)
+                .ok()
+                .and_then(|size| size.checked_add(4 + EOCD64_CONSTANT_SIZE))
+                .ok_or(CentralDirectoryReadError::BadEOCD64)?;
+
+            let eocd64_end = global_offset(disk_sizes, eocd64_pos, is_cut)
+                .ok_or(CentralDirectoryReadError::Map)?
+                .checked_add(eocd64_length)
+                .ok_or(CentralDirectoryReadError::BadEOCD64)?;
+            if eocd64_end > locator_global_offset {
+                return Err(CentralDirectoryReadError::BadEOCD64);
+            }
+
+            let eocd64_bytes = make_calls(map_to_calls(disk_sizes, eocd64_pos, eocd64_length, is_cut)?, provider)?;
+            found_eocd64 = EndOfCentralDirectory64::from_bytes(&eocd64_bytes[4..])
+                .ok_or(CentralDirectoryReadError::BadEOCD64)?;
+        }
 
-        let eocd64_signature = u32::from_le_bytes(eocd64_bytes[..4].try_into().unwrap());
-        if eocd64_signature != EOCD64_SIGNATURE {
-            return Err(CentralDirectoryReadError::BadEOCD64);
+        // An encrypted or compressed central directory can't be parsed
+        if let Some(feature) = found_eocd64.extensible_data.as_ref().and_then(|e| e.unsupported_feature()) {
+            return Err(CentralDirectoryReadError::UnsupportedFeature {
+                feature,
+                entry: None
+            });
         }
 
-        let eocd64 = EndOfCentralDirectory64::from_bytes(&eocd64_bytes[4..])
-            .ok_or(CentralDirectoryReadError::BadEOCD64)?;
+        let cdld = CentralDirectoryLocationData::from_eocd64(eocd32.clone(), found_eocd64.clone());
+        eocd64 = Some(found_eocd64);
+        eocd64_locator = Some(locator);
 
-        CentralDirectoryLocationData::from_eocd64(eocd32, eocd64)
+        (cdld, eocd64_pos.offset)
     };
 
-    let cd_pos = ZipPosition::new(
-        cdld.cd_disk_number as usize,
-        cdld.cd_offset as usize
-    );
-    let cd_bytes = make_calls(map_to_calls(disk_sizes, cd_pos, cdld.cd_size as usize, is_cut)?, &provider)?;
+    let declared_cd_end = cdld.cd_offset.checked_add(cdld.cd_size).ok_or(if eocd64.is_some() {
+        CentralDirectoryReadError::BadEOCD64
+    } else {
+        CentralDirectoryReadError::BadEOCD32
+    })?;
+
+    let mut shift = 0;
+    if let Some(difference) = (cd_end as u64).checked_sub(declared_cd_end) {
+        // The difference may also be caused by zip64 records which are not
+        // required, so only accept it if there actually is a CDFH at the new offset
+        if is_single && difference != 0 && cdld.cd_size >= 4 {
+            let pos = ZipPosition::from_offset((cdld.cd_offset + difference) as usize);
+            if has_signature(disk_sizes, pos, CDFH_SIGNATURE, is_cut, provider)? {
+                shift = difference;
+            }
+        }
+    }
 
-    Ok(CentralDirectory::from_bytes(cd_bytes)?)
+    // An empty central directory can't be checked, so it must be right before the EOCD
+    if cdld.cd_size == 0 && is_single && cd_end as u64 != cdld.cd_offset {
+        return Err(CentralDirectoryReadError::DecodeCentralDirectory(CentralDirectoryError::InputTooShort));
+    }
+
+    if shift == 0 && cdld.cd_size != 0 {
+        let pos = ZipPosition::new(cdld.cd_disk_number as usize, cdld.cd_offset as usize);
+        if !has_signature(disk_sizes, pos, CDFH_SIGNATURE, is_cut, provider)? {
+            // The offsets on the first disk may not account for a spanning marker
+            let marker_pos = pos.offset.checked_add(SPANNING_MARKER_SIZE).map(|offset| ZipPosition::new(pos.disk, offset));
+            let is_after_marker = match marker_pos {
+                Some(marker_pos) if pos.disk == 0 => has_spanning_marker(disk_sizes, is_cut, provider)? && has_signature(disk_sizes, marker_pos, CDFH_SIGNATURE, is_cut, provider)?,
+                _ => false
+            };
+
+            if is_after_marker {
+                shift = SPANNING_MARKER_SIZE as u64;
+            } else {
+                return Err(CentralDirectoryReadError::DecodeCentralDirectory(CentralDirectoryError::InvalidSignature(0)));
+            }
+        }
+    }
+
+    Ok(LocatedCentralDirectory {
+        eocd32,
+        eocd64_locator,
+        eocd64,
+        cdld,
+        shift
+    })
+}
+
+/// Checks whether the first disk starts with a spanning marker
+fn has_spanning_marker(disk_sizes: &[usize], is_cut: bool, provider: &dyn Fn(ZipPosition, usize) -> Result<Vec<u8>, anyhow::Error>) -> Result<bool, CentralDirectoryReadError> {
+    let Ok(calls) = map_to_calls(disk_sizes, ZipPosition::default(), SPANNING_MARKER_SIZE, is_cut) else {
+        return Ok(false);
+    };
+
+    Ok(starts_with_spanning_marker(make_calls(calls, provider)?))
+}
+
+/// Checks whether there is the specified signature at the specified position
+fn has_signature(disk_sizes: &[usize], pos: ZipPosition, signature: u32, is_cut: bool, provider: &dyn Fn(ZipPosition, usize) -> Result<Vec<u8>, anyhow::Error>) -> Result<bool, CentralDirectoryReadError> {
+    let Ok(calls) = map_to_calls(disk_sizes, pos, 4, is_cut) else {
+        return Ok(false);
+    };
+    let signature_bytes = make_calls(calls, provider)?;
+
+    Ok(u32::from_le_bytes(signature_bytes[..4].try_into().unwrap()) == signature)
+}
+
+/// Finds the offset of an EOCD64 signature whose size field
+/// says that the record ends exactly at the specified offset
+fn find_eocd64_before(data: &[u8], end: usize) -> Option<usize> {
+    (0..end.checked_sub(4 + EOCD64_CONSTANT_SIZE)? + 1)
+        .rev()
+        .find(|offset| {
+            let signature = u32::from_le_bytes(data[*offset..(*offset + 4)].try_into().unwrap());
+            let size = u64::from_le_bytes(data[(*offset + 4)..(*offset + 12)].try_into().unwrap());
+
+            signature == EOCD64_SIGNATURE && (*offset as u64).checked_add(12).and_then(|o| o.checked_add(size)) == Some(end as u64)
+        })
+}
+
+/// Reads the EOCD64 at the specified position. Returns None if the signature is wrong
+fn read_eocd64(disk_sizes: &[usize], pos: ZipPosition, is_cut: bool, provider: &dyn Fn(ZipPosition, usize) -> Result<Vec<u8>, anyhow::Error>) -> Result<Option<EndOfCentralDirectory64>, CentralDirectoryReadError> {
+    let eocd64_bytes = make_calls(map_to_calls(disk_sizes, pos, 4 + EOCD64_CONSTANT_SIZE, is_cut)?, provider)?;
+
+    let eocd64_signature = u32::from_le_bytes(eocd64_bytes[..4].try_into().unwrap());
+    if eocd64_signature != EOCD64_SIGNATURE {
+        return Ok(None);
+    }
+
+    EndOfCentralDirectory64::from_bytes(&eocd64_bytes[4..])
+        .ok_or(CentralDirectoryReadError::BadEOCD64)
+        .map(Some)
 }
 
 #[inline]
@@ -116,8 +488,21 @@ fn make_calls(calls: Vec<(ZipPosition, usize)>, provider: &dyn Fn(ZipPosition, u
     Ok(bytes)
 }
 
+/// Converts a position into an offset from the start of the first disk
+fn global_offset(disk_sizes: &[usize], pos: ZipPosition, is_cut: bool) -> Option<usize> {
+    if is_cut {
+        return Some(pos.offset);
+    }
+
+    (pos.disk < disk_sizes.len()).then(|| disk_sizes[..pos.disk].iter().sum::<usize>() + pos.offset)
+}
+
 #[inline]
 fn map_local_to_calls(disk_sizes: &[usize], pos: ZipPosition, length: usize) -> Result<Vec<(ZipPosition, usize)>, CentralDirectoryReadError> {
+    if disk_sizes.get(pos.disk).is_none_or(|size| pos.offset >= *size) {
+        return Err(CentralDirectoryReadError::Map);
+    }
+
     let mut out = Vec::new();
 
     let mut cur_offset = pos.offset;
@@ -134,7 +519,7 @@ fn map_local_to_calls(disk_sizes: &[usize], pos: ZipPosition, length: usize) ->
         cur_offset = 0;
     }
 
-    if !out.is_empty() {
+    if left == 0 {
         Ok(out)
     } else {
         Err(CentralDirectoryReadError::Map)
@@ -162,3 +547,239 @@ fn map_to_calls(disk_sizes: &[usize], pos: ZipPosition, length: usize, global: b
         map_global_to_calls(disk_sizes, pos.offset, length)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{from_provider_with_info, ArchiveInfo, CentralDirectoryReadError};
+    use crate::zip::structures::central_directory::{CentralDirectory, CentralDirectoryError};
+
+    /// The local headers and the central directory of an archive with stored files
+    struct Entries {
+        bytes: Vec<u8>,
+        count: u16,
+        cd_offset: u64,
+        cd_size: u64
+    }
+
+    /// Builds the entries of an archive. "header_offset_base" is added to the LFH offsets
+    /// in the CDFHs, and offsets of u32::MAX or more are stored in a ZIP64 extra field
+    fn entries(files: &[(&str, &[u8])], header_offset_base: u64) -> Entries {
+        let mut bytes = Vec::new();
+        let mut cd = Vec::new();
+
+        for (name, data) in files {
+            let offset = header_offset_base + bytes.len() as u64;
+            let crc32 = crc32fast::hash(data);
+
+            bytes.extend_from_slice(&0x04034b50u32.to_le_bytes());
+            bytes.extend_from_slice(&[20, 0, 0, 0, 0, 0, 0, 0, 0x21, 0]);
+            bytes.extend_from_slice(&crc32.to_le_bytes());
+            bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(&0u16.to_le_bytes());
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.extend_from_slice(data);
+
+            cd.extend_from_slice(&0x02014b50u32.to_le_bytes());
+            cd.extend_from_slice(&[20, 0, 20, 0, 0, 0, 0, 0, 0, 0, 0x21, 0]);
+            cd.extend_from_slice(&crc32.to_le_bytes());
+            cd.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            cd.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            cd.extend_from_slice(&(name.len() as u16).to_le_bytes());
+
+            if offset >= u32::MAX as u64 {
+                cd.extend_from_slice(&12u16.to_le_bytes());
+                cd.extend_from_slice(&[0; 6]);
+                cd.extend_from_slice(&0u32.to_le_bytes());
+                cd.extend_from_slice(&u32::MAX.to_le_bytes());
+                cd.extend_from_slice(name.as_bytes());
+
+                cd.extend_from_slice(&1u16.to_le_bytes());
+                cd.extend_from_slice(&8u16.to_le_bytes());
+                cd.extend_from_slice(&offset.to_le_bytes());
+            } else {
+                cd.extend_from_slice(&0u16.to_le_bytes());
+                cd.extend_from_slice(&[0; 6]);
+                cd.extend_from_slice(&0u32.to_le_bytes());
+                cd.extend_from_slice(&(offset as u32).to_le_bytes());
+                cd.extend_from_slice(name.as_bytes());
+            }
+        }
+
+        let cd_offset = bytes.len() as u64;
+        let cd_size = cd.len() as u64;
+        bytes.extend(cd);
+
+        Entries {
+            bytes,
+            count: files.len() as u16,
+            cd_offset,
+            cd_size
+        }
+    }
+
+    fn eocd32(count: u16, cd_size: u32, cd_offset: u32, comment: &[u8]) -> Vec<u8> {
+        let mut bytes = 0x06054b50u32.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.extend_from_slice(&count.to_le_bytes());
+        bytes.extend_from_slice(&count.to_le_bytes());
+        bytes.extend_from_slice(&cd_size.to_le_bytes());
+        bytes.extend_from_slice(&cd_offset.to_le_bytes());
+        bytes.extend_from_slice(&(comment.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(comment);
+        bytes
+    }
+
+    /// Appends the EOCD64, its locator and an EOCD32 which refers to them
+    fn append_zip64_records(bytes: &mut Vec<u8>, count: u64, cd_size: u64, cd_offset: u64) {
+        let eocd64_offset = bytes.len() as u64;
+
+        bytes.extend_from_slice(&0x06064b50u32.to_le_bytes());
+        bytes.extend_from_slice(&44u64.to_le_bytes());
+        bytes.extend_from_slice(&[45, 0, 45, 0]);
+        bytes.extend_from_slice(&[0; 8]);
+        bytes.extend_from_slice(&count.to_le_bytes());
+        bytes.extend_from_slice(&count.to_le_bytes());
+        bytes.extend_from_slice(&cd_size.to_le_bytes());
+        bytes.extend_from_slice(&cd_offset.to_le_bytes());
+
+        bytes.extend_from_slice(&0x07064b50u32.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&eocd64_offset.to_le_bytes());
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+
+        bytes.extend(eocd32(u16::MAX, u32::MAX, u32::MAX, b""));
+    }
+
+    fn archive(files: &[(&str, &[u8])], comment: &[u8]) -> Vec<u8> {
+        let entries = entries(files, 0);
+
+        let mut bytes = entries.bytes;
+        bytes.extend(eocd32(entries.count, entries.cd_size as u32, entries.cd_offset as u32, comment));
+        bytes
+    }
+
+    fn read(archive: &[u8]) -> Result<(CentralDirectory, ArchiveInfo), CentralDirectoryReadError> {
+        from_provider_with_info(
+            vec![archive.len()],
+            false,
+            |pos, length| Ok(archive[pos.offset..(pos.offset + length)].to_vec())
+        )
+    }
+
+    fn filenames(central_directory: &CentralDirectory) -> Vec<&str> {
+        central_directory.headers_ref().iter().map(|h| h.filename.as_str()).collect()
+    }
+
+    const FILES: [(&str, &[u8]); 2] = [("a.txt", b"first file"), ("b.txt", b"second file")];
+
+    #[test]
+    fn reads_central_directory() {
+        let (central_directory, info) = read(&archive(&FILES, b"")).unwrap();
+
+        assert_eq!(filenames(&central_directory), ["a.txt", "b.txt"]);
+        assert_eq!(info.prefix_length, 0);
+        assert!(!info.is_zip64());
+    }
+
+    #[test]
+    fn skips_fake_eocd_in_comment() {
+        // A fake EOCD at the very end of the comment, pointing at a LFH instead of the CD
+        let fake = eocd32(1, 46, 0, b"");
+        let archive = archive(&FILES, &[&b"comment "[..], &fake].concat());
+
+        let (central_directory, _) = read(&archive).unwrap();
+        assert_eq!(filenames(&central_directory), ["a.txt", "b.txt"]);
+    }
+
+    #[test]
+    fn ignores_trailing_junk() {
+        let mut archive = archive(&FILES, b"comment");
+        archive.extend_from_slice(b"some trailing junk");
+
+        let (central_directory, info) = read(&archive).unwrap();
+        assert_eq!(filenames(&central_directory), ["a.txt", "b.txt"]);
+        assert_eq!(info.prefix_length, 0);
+    }
+
+    #[test]
+    fn ignores_fake_eocd_in_trailing_junk() {
+        let mut archive = archive(&FILES, b"");
+        archive.extend(eocd32(1, 46, 0, b""));
+
+        let (central_directory, _) = read(&archive).unwrap();
+        assert_eq!(filenames(&central_directory), ["a.txt", "b.txt"]);
+    }
+
+    #[test]
+    fn fails_without_eocd() {
+        let archive = entries(&FILES, 0).bytes;
+        assert!(matches!(read(&archive), Err(CentralDirectoryReadError::NoEOCD32)));
+    }
+
+    #[test]
+    fn detects_prepended_data() {
+        let archive = [&[0x4d, 0x5a][..], &[0; 98], &archive(&FILES, b"")].concat();
+        let (central_directory, info) = read(&archive).unwrap();
+
+        assert_eq!(filenames(&central_directory), ["a.txt", "b.txt"]);
+        assert_eq!(info.prefix_length, 100);
+        assert_eq!(central_directory.headers_ref()[0].local_header_offset, 100);
+        assert_eq!(info.cd_position.offset as u64, 100 + entries(&FILES, 0).cd_offset);
+    }
+
+    #[test]
+    fn detects_prepended_data_with_zip64_records() {
+        let entries = entries(&FILES, 0);
+        let mut zip64_archive = entries.bytes;
+        append_zip64_records(&mut zip64_archive, entries.count as u64, entries.cd_size, entries.cd_offset);
+
+        let (central_directory, info) = read(&zip64_archive).unwrap();
+        assert!(info.is_zip64());
+        assert_eq!(info.prefix_length, 0);
+        assert_eq!(central_directory.headers_ref()[1].local_header_offset, 45);
+
+        let archive = [&[0x4d, 0x5a][..], &[0; 98], &zip64_archive].concat();
+        let (central_directory, info) = read(&archive).unwrap();
+        assert!(info.is_zip64());
+        assert_eq!(info.prefix_length, 100);
+        assert_eq!(central_directory.headers_ref()[1].local_header_offset, 145);
+    }
+
+    #[test]
+    fn rejects_overflowing_central_directory_bounds() {
+        let entries = entries(&FILES, 0);
+        let mut archive = entries.bytes;
+        append_zip64_records(&mut archive, entries.count as u64, 0x100, 0xFFFFFFFFFFFFFFF0);
+
+        assert!(matches!(read(&archive), Err(CentralDirectoryReadError::BadEOCD64)));
+    }
+
+    #[test]
+    fn rejects_central_directory_at_the_end_of_the_address_space() {
+        // The archive starts with a spanning marker, so the offset
+        // is also probed after it, which must not overflow either
+        let entries = entries(&FILES, 4);
+        let mut archive = [&0x08074b50u32.to_le_bytes()[..], &entries.bytes].concat();
+        append_zip64_records(&mut archive, entries.count as u64, 2, u64::MAX - 2);
+
+        assert!(matches!(
+            read(&archive),
+            Err(CentralDirectoryReadError::DecodeCentralDirectory(CentralDirectoryError::InvalidSignature(0)))
+        ));
+    }
+
+    #[test]
+    fn rejects_overflowing_shifted_offsets() {
+        let entries = entries(&FILES[..1], u64::MAX - 10);
+        let mut archive = entries.bytes;
+        append_zip64_records(&mut archive, entries.count as u64, entries.cd_size, entries.cd_offset);
+
+        let archive = [&[0; 100][..], &archive].concat();
+        assert!(matches!(
+            read(&archive),
+            Err(CentralDirectoryReadError::DecodeCentralDirectory(CentralDirectoryError::OffsetOverflow(name))) if name == "a.txt"
+        ));
+    }
+}
@@ -0,0 +1,146 @@
+/// Represents the system which created a file, stored in
+/// the upper byte of the "version made by" field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HostSystem {
+    MsDos,
+    Unix,
+    Ntfs,
+    Vfat,
+    MacOsX,
+
+    Other(u8)
+}
+
+impl HostSystem {
+    /// Turns the upper byte of the "version made by" field into a [HostSystem] variant
+    pub fn from_id(id: u8) -> Self {
+        match id {
+            0 => Self::MsDos,
+            3 => Self::Unix,
+            10 => Self::Ntfs,
+            14 => Self::Vfat,
+            19 => Self::MacOsX,
+
+            _ => Self::Other(id)
+        }
+    }
+
+    /// Returns whether the external attributes contain a Unix mode when made by this system
+    pub fn has_unix_mode(&self) -> bool {
+        matches!(self, Self::Unix | Self::MacOsX)
+    }
+}
+
+/// The type of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EntryKind {
+    File,
+    Directory,
+    Symlink,
+    Fifo,
+    CharDevice,
+    BlockDevice,
+    Socket
+}
+
+pub const UNIX_FILE_TYPE_MASK: u32 = 0o170000;
+pub const UNIX_PERMISSIONS_MASK: u32 = 0o7777;
+
+impl EntryKind {
+    /// Determines the type of a file from the file type bits of a Unix mode.
+    /// Returns None if they are not set or unknown
+    pub fn from_unix_mode(mode: u32) -> Option<Self> {
+        match mode & UNIX_FILE_TYPE_MASK {
+            0o100000 => Some(Self::File),
+            0o040000 => Some(Self::Directory),
+            0o120000 => Some(Self::Symlink),
+            0o010000 => Some(Self::Fifo),
+            0o020000 => Some(Self::CharDevice),
+            0o060000 => Some(Self::BlockDevice),
+            0o140000 => Some(Self::Socket),
+
+            _ => None
+        }
+    }
+}
+
+pub const DOS_ATTRIBUTE_READ_ONLY: u8 = 0x01;
+pub const DOS_ATTRIBUTE_HIDDEN: u8 = 0x02;
+pub const DOS_ATTRIBUTE_SYSTEM: u8 = 0x04;
+pub const DOS_ATTRIBUTE_DIRECTORY: u8 = 0x10;
+pub const DOS_ATTRIBUTE_ARCHIVE: u8 = 0x20;
+
+/// Set by some Windows archivers if the upper 16 bits of the external attributes contain a Unix mode
+pub const UNIX_EXTENSION_ATTRIBUTE: u32 = 0x8000;
+
+/// MS-DOS file attributes, stored in the lowest byte of the external attributes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DosAttributes(pub u8);
+
+impl DosAttributes {
+    pub fn is_read_only(&self) -> bool {
+        self.0 & DOS_ATTRIBUTE_READ_ONLY != 0
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        self.0 & DOS_ATTRIBUTE_HIDDEN != 0
+    }
+
+    pub fn is_system(&self) -> bool {
+        self.0 & DOS_ATTRIBUTE_SYSTEM != 0
+    }
+
+    pub fn is_directory(&self) -> bool {
+        self.0 & DOS_ATTRIBUTE_DIRECTORY != 0
+    }
+
+    pub fn is_archive(&self) -> bool {
+        self.0 & DOS_ATTRIBUTE_ARCHIVE != 0
+    }
+}
+
+/// Represents the result of interpreting the external attributes of a file
+/// according to the system which created it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileAttributes {
+    pub host_system: HostSystem,
+
+    /// The Unix mode (st_mode) of the file, if it is stored
+    pub unix_mode: Option<u32>,
+
+    pub dos_attributes: DosAttributes
+}
+
+impl FileAttributes {
+    /// Interprets the external attributes according to the upper byte of "version made by"
+    pub fn new(version_made_by: u16, external_attributes: u32) -> Self {
+        let host_system = HostSystem::from_id((version_made_by >> 8) as u8);
+
+        let mode = external_attributes >> 16;
+        let has_unix_mode = host_system.has_unix_mode() || external_attributes & UNIX_EXTENSION_ATTRIBUTE != 0;
+        let unix_mode = (has_unix_mode && mode != 0).then_some(mode);
+
+        Self {
+            host_system,
+            unix_mode,
+            dos_attributes: DosAttributes(external_attributes as u8)
+        }
+    }
+
+    /// Returns the type of the file, or None if it can only be determined from the filename.
+    /// Without a Unix mode, only files and directories can be distinguished
+    pub fn kind(&self) -> Option<EntryKind> {
+        if let Some(kind) = self.unix_mode.and_then(EntryKind::from_unix_mode) {
+            return Some(kind);
+        }
+
+        self.dos_attributes.is_directory().then_some(EntryKind::Directory)
+    }
+
+    /// Returns the Unix permission bits (including setuid, setgid and sticky), if they are stored
+    pub fn unix_permissions(&self) -> Option<u32> {
+        self.unix_mode.map(|m| m & UNIX_PERMISSIONS_MASK)
+    }
+}
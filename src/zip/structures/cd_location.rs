@@ -2,6 +2,11 @@ use std::io::Cursor;
 
 use byteorder::{LittleEndian, ReadBytesExt};
 
+use super::file_header::UnsupportedFeature;
+
+#[cfg(feature = "zip-comments")]
+use super::encoding::decode_header_text;
+
 pub const CDLD_MAX_SIZE: usize = EOCD32_MAX_SIZE + 4 + EOCD64_LOCATOR_CONSTANT_SIZE;
 
 #[derive(Debug, Clone)]
@@ -11,7 +16,11 @@ pub struct CentralDirectoryLocationData {
     pub cd_offset: u64,
 
     #[cfg(feature = "zip-comments")]
-    pub comment: String
+    pub comment: String,
+
+    /// The original bytes of the comment, before any decoding
+    #[cfg(feature = "zip-comments")]
+    pub comment_raw: Vec<u8>
 }
 
 impl CentralDirectoryLocationData {
@@ -22,7 +31,10 @@ impl CentralDirectoryLocationData {
             cd_offset: eocd32.cd_offset as u64,
 
             #[cfg(feature = "zip-comments")]
-            comment: eocd32.comment
+            comment: eocd32.comment,
+
+            #[cfg(feature = "zip-comments")]
+            comment_raw: eocd32.comment_raw
         }
     }
 
@@ -60,9 +72,15 @@ pub struct EndOfCentralDirectory32 {
     pub cd_size: u32,
     pub cd_offset: u32,
 
+    /// The archive comment. There is no flag for UTF-8 in the
+    /// EOCD, so it is always decoded as CP437
     #[cfg(feature = "zip-comments")]
     pub comment: String,
 
+    /// The original bytes of the comment, before any decoding
+    #[cfg(feature = "zip-comments")]
+    pub comment_raw: Vec<u8>,
+
     pub eocd32_size: usize
 }
 
@@ -99,7 +117,10 @@ impl EndOfCentralDirectory32 {
             cd_offset,
 
             #[cfg(feature = "zip-comments")]
-            comment: String::from_utf8_lossy(&data[comment_start..comment_end]).to_string(),
+            comment: decode_header_text(&data[comment_start..comment_end], false),
+
+            #[cfg(feature = "zip-comments")]
+            comment_raw: data[comment_start..comment_end].to_vec(),
 
             eocd32_size: comment_end
         })
@@ -110,21 +131,26 @@ impl EndOfCentralDirectory32 {
     /// 
     /// Returns the offset of this structure within the data if it is found
     pub fn find_offset(data: impl AsRef<[u8]>) -> Option<usize> {
+        Self::find_offsets(data).into_iter().next()
+    }
+
+    /// Finds all possible end of central directory structures at the end of given data,
+    /// since the signature may also appear inside the archive comment or trailing data.
+    /// This searches at most 2^16 bytes
+    /// 
+    /// Returns the offsets of the signatures within the data, starting from the last one
+    pub fn find_offsets(data: impl AsRef<[u8]>) -> Vec<usize> {
         let data = data.as_ref();
         if data.len() < EOCD32_CONSTANT_SIZE {
-            return None;
+            return Vec::new();
         }
 
         let first_offset = data.len() - std::cmp::min(data.len(), EOCD32_MAX_SIZE);
 
-        for offset in (first_offset..(data.len() - EOCD32_CONSTANT_SIZE)).rev() {
-            let signature = u32::from_le_bytes(data[offset..(offset + 4)].try_into().unwrap());
-            if signature == EOCD32_SIGNATURE {
-                return Some(offset);
-            }
-        }
-
-        None
+        (first_offset..(data.len() - EOCD32_CONSTANT_SIZE))
+            .rev()
+            .filter(|offset| u32::from_le_bytes(data[*offset..(*offset + 4)].try_into().unwrap()) == EOCD32_SIGNATURE)
+            .collect()
     }
 
     pub fn requires_zip64(&self) -> bool {
@@ -186,7 +212,11 @@ pub struct EndOfCentralDirectory64 {
     pub cd_entry_count_total: u64,
 
     pub cd_size: u64,
-    pub cd_offset: u64
+    pub cd_offset: u64,
+
+    /// The extensible data sector. Only present if it was included in the
+    /// data passed to [EndOfCentralDirectory64::from_bytes] and is not empty
+    pub extensible_data: Option<Eocd64ExtensibleData>
 }
 
 impl EndOfCentralDirectory64 {
@@ -208,7 +238,10 @@ impl EndOfCentralDirectory64 {
         let cd_size = cursor.read_u64::<LittleEndian>().unwrap();
         let cd_offset = cursor.read_u64::<LittleEndian>().unwrap();
 
-        // TODO: extensible data field
+        let extensible_data_size = usize::try_from(Self::extensible_data_size_from(eocd64_size)).ok()?;
+        let extensible_data_end = EOCD64_CONSTANT_SIZE.checked_add(extensible_data_size)?;
+        let extensible_data = (extensible_data_size != 0 && data.len() >= extensible_data_end)
+            .then(|| Eocd64ExtensibleData::from_bytes(&data[EOCD64_CONSTANT_SIZE..extensible_data_end], version_needed));
 
         Some(Self {
             eocd64_size,
@@ -219,7 +252,190 @@ impl EndOfCentralDirectory64 {
             cd_entry_count,
             cd_entry_count_total,
             cd_size,
-            cd_offset
+            cd_offset,
+            extensible_data
+        })
+    }
+
+    /// Returns the size of the extensible data sector which follows the fixed fields
+    pub fn extensible_data_size(&self) -> u64 {
+        Self::extensible_data_size_from(self.eocd64_size)
+    }
+
+    fn extensible_data_size_from(eocd64_size: u64) -> u64 {
+        // The size field does not include itself and the signature
+        eocd64_size.saturating_sub((EOCD64_CONSTANT_SIZE - 8) as u64)
+    }
+}
+
+pub const EOCD64_V2_CONSTANT_SIZE: usize = 28;
+pub const EOCD64_V2_MIN_VERSION: u16 = 62;
+pub const STRONG_ENCRYPTION_RECORD_ID: u16 = 0x0017;
+
+/// The additional fields of a version 2 EOCD64, which is written
+/// if the central directory is compressed or encrypted
+#[derive(Debug, Clone)]
+pub struct Eocd64Version2 {
+    pub compression_method: u16,
+
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+
+    pub encryption_algorithm: u16,
+    pub bit_length: u16,
+    pub flags: u16,
+
+    pub hash_algorithm: u16,
+    pub hash_data: Vec<u8>
+}
+
+/// A record from the EOCD64 extensible data sector
+#[derive(Debug, Clone)]
+pub struct Eocd64ExtensibleRecord {
+    pub id: u16,
+    pub data: Vec<u8>
+}
+
+/// The strong encryption header record (0x0017). Its presence
+/// means that the central directory is encrypted
+#[derive(Debug, Clone)]
+pub struct StrongEncryptionHeader {
+    pub format: u16,
+    pub algorithm_id: u16,
+    pub bit_length: u16,
+    pub flags: u16,
+    pub certificate_data: Vec<u8>
+}
+
+/// Represents the EOCD64 extensible data sector
+#[derive(Debug, Clone)]
+pub struct Eocd64ExtensibleData {
+    /// The version 2 fields, if the EOCD64 has version 6.2 or later
+    pub version2: Option<Eocd64Version2>,
+
+    /// The records which could be read. Records which don't fit are ignored
+    pub records: Vec<Eocd64ExtensibleRecord>,
+
+    /// The entire sector, including the version 2 fields
+    pub raw: Vec<u8>
+}
+
+impl Eocd64ExtensibleData {
+    /// Reads the extensible data sector. The version needed to extract
+    /// determines whether it starts with the version 2 fields
+    pub fn from_bytes(data: impl AsRef<[u8]>, version_needed: u16) -> Self {
+        let data = data.as_ref();
+
+        let version2 = ((version_needed & 0xFF) >= EOCD64_V2_MIN_VERSION)
+            .then(|| Eocd64Version2::from_bytes(data))
+            .flatten();
+
+        // Records follow the version 2 fields
+        let mut offset = version2.as_ref().map_or(0, |v2| EOCD64_V2_CONSTANT_SIZE + v2.hash_data.len());
+
+        let mut records = Vec::new();
+        while offset + 6 <= data.len() {
+            let mut cursor = Cursor::new(&data[offset..]);
+            let id = cursor.read_u16::<LittleEndian>().unwrap();
+            let size = cursor.read_u32::<LittleEndian>().unwrap() as usize;
+
+            let data_start = offset + 6;
+            let Some(data_end) = data_start.checked_add(size).filter(|e| *e <= data.len()) else {
+                break;
+            };
+
+            records.push(Eocd64ExtensibleRecord {
+                id,
+                data: data[data_start..data_end].to_owned()
+            });
+
+            offset = data_end;
+        }
+
+        Self {
+            version2,
+            records,
+            raw: data.to_owned()
+        }
+    }
+
+    /// Returns the unsupported feature used by the central directory, if there is one
+    pub fn unsupported_feature(&self) -> Option<UnsupportedFeature> {
+        let version2 = self.version2.as_ref();
+
+        if self.strong_encryption_header().is_some() || version2.is_some_and(|v2| v2.encryption_algorithm != 0) {
+            Some(UnsupportedFeature::CentralDirectoryEncryption)
+        } else if version2.is_some_and(|v2| v2.compression_method != 0) {
+            Some(UnsupportedFeature::CentralDirectoryCompression)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the strong encryption header record, if there is one
+    pub fn strong_encryption_header(&self) -> Option<StrongEncryptionHeader> {
+        self.records
+            .iter()
+            .find(|r| r.id == STRONG_ENCRYPTION_RECORD_ID)
+            .and_then(|r| StrongEncryptionHeader::from_bytes(&r.data))
+    }
+}
+
+impl Eocd64Version2 {
+    pub fn from_bytes(data: impl AsRef<[u8]>) -> Option<Self> {
+        let data = data.as_ref();
+        if data.len() < EOCD64_V2_CONSTANT_SIZE {
+            return None;
+        }
+
+        let mut cursor = Cursor::new(data);
+
+        let compression_method = cursor.read_u16::<LittleEndian>().unwrap();
+        let compressed_size = cursor.read_u64::<LittleEndian>().unwrap();
+        let uncompressed_size = cursor.read_u64::<LittleEndian>().unwrap();
+        let encryption_algorithm = cursor.read_u16::<LittleEndian>().unwrap();
+        let bit_length = cursor.read_u16::<LittleEndian>().unwrap();
+        let flags = cursor.read_u16::<LittleEndian>().unwrap();
+        let hash_algorithm = cursor.read_u16::<LittleEndian>().unwrap();
+        let hash_length = cursor.read_u16::<LittleEndian>().unwrap() as usize;
+
+        if data.len() < EOCD64_V2_CONSTANT_SIZE + hash_length {
+            return None;
+        }
+
+        Some(Self {
+            compression_method,
+            compressed_size,
+            uncompressed_size,
+            encryption_algorithm,
+            bit_length,
+            flags,
+            hash_algorithm,
+            hash_data: data[EOCD64_V2_CONSTANT_SIZE..(EOCD64_V2_CONSTANT_SIZE + hash_length)].to_owned()
+        })
+    }
+}
+
+impl StrongEncryptionHeader {
+    pub fn from_bytes(data: impl AsRef<[u8]>) -> Option<Self> {
+        let data = data.as_ref();
+        if data.len() < 8 {
+            return None;
+        }
+
+        let mut cursor = Cursor::new(data);
+
+        let format = cursor.read_u16::<LittleEndian>().unwrap();
+        let algorithm_id = cursor.read_u16::<LittleEndian>().unwrap();
+        let bit_length = cursor.read_u16::<LittleEndian>().unwrap();
+        let flags = cursor.read_u16::<LittleEndian>().unwrap();
+
+        Some(Self {
+            format,
+            algorithm_id,
+            bit_length,
+            flags,
+            certificate_data: data[8..].to_owned()
         })
     }
 }
@@ -1,13 +1,17 @@
-use std::io::Cursor;
+use std::{io::Cursor, path::PathBuf, time::SystemTime};
 
 use byteorder::{ReadBytesExt, LittleEndian};
 use thiserror::Error;
 
 use crate::zip::ZipPosition;
 
-use super::{CompressionMethod, file_header::{FileHeaderExtraField, Zip64OriginalData, Zip64ProcessedData}};
+use super::{CompressionMethod, file_header::{UnsupportedFeature, unsupported_feature, FileHeaderExtraField, Zip64OriginalData, ZIP64_EXTRA_FIELD_ID, Zip64ProcessedData, FLAG_ENCRYPTED, AesExtraField, ExtendedTimestamp, NtfsTimestamps, UnixOwnership, TextSource, UNICODE_PATH_EXTRA_FIELD_ID, decode_text}, date_time::{ZipDateTime, ZipTimeZone}, extra_field::{ExtraField, ExtraFieldRegistry}, attributes::{FileAttributes, EntryKind}, local_file_header::LFH_CONSTANT_SIZE, path::{sanitize_path, PathSanitizationError}};
+
+#[cfg(feature = "zip-comments")]
+use super::file_header::UNICODE_COMMENT_EXTRA_FIELD_ID;
 
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum CentralDirectoryError {
     #[error("input too short")]
     InputTooShort,
@@ -19,9 +23,31 @@ pub enum CentralDirectoryError {
     MalformedHeader(usize),
 
     #[error("{0} bytes left over after reading entire central directory")]
-    LeftoverBytes(usize)
+    LeftoverBytes(usize),
+
+    #[error("local header offset of {0} overflows after adjusting for prepended data")]
+    OffsetOverflow(String)
+}
+
+/// A problem with the placement of an entry, found by [SortedCentralDirectory::validate_layout].
+/// Entries are referred to by their index in [SortedCentralDirectory::headers_ref]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LayoutIssue {
+    /// The data of the first entry overlaps the second one
+    Overlap(usize, usize),
+
+    /// The entry overlaps the central directory or is located after it
+    OverlapsCentralDirectory(usize),
+
+    /// The entry extends past the end of the last disk
+    OutOfBounds(usize)
 }
 
+#[derive(Debug, Error)]
+#[error("invalid archive layout: {0:?}")]
+pub struct LayoutError(pub Vec<LayoutIssue>);
+
 /// Represents a ZIP central directory.
 /// 
 /// The unpacker requires the central directory to be sorted
@@ -72,6 +98,21 @@ impl CentralDirectory {
         &self.headers
     }
 
+    /// Moves the LFH offsets of all files on the specified disk forward. Data
+    /// prepended to a disk only moves the files stored on that disk, since
+    /// the offsets are relative to the start of the disk the LFH is located on
+    pub(crate) fn shift_disk_offsets(&mut self, disk_number: u32, shift: u64) -> Result<(), CentralDirectoryError> {
+        if let Some(cdfh) = self.headers.iter().find(|h| h.disk_number == disk_number && h.local_header_offset.checked_add(shift).is_none()) {
+            return Err(CentralDirectoryError::OffsetOverflow(cdfh.filename.clone()));
+        }
+
+        for cdfh in self.headers.iter_mut().filter(|h| h.disk_number == disk_number) {
+            cdfh.local_header_offset += shift;
+        }
+
+        Ok(())
+    }
+
     pub fn sort(mut self) -> SortedCentralDirectory {
         self.headers.sort_by(|a, b| {
             a.header_position().cmp(&b.header_position())    
@@ -94,6 +135,67 @@ impl SortedCentralDirectory {
     pub fn headers_ref(&self) -> &[CentralDirectoryFileHeader] {
         &self.headers
     }
+
+    /// Checks that no entries overlap each other or the central directory (if its
+    /// position is provided), and that all entries fit within the disks. This should
+    /// be done before streaming, since the unpacker can only detect these problems
+    /// once it reaches them
+    /// 
+    /// Disk sizes must be the same as the ones passed to the unpacker. The extent of
+    /// each entry is estimated from the CDFH, so it doesn't include the LFH extra fields
+    /// and the data descriptor
+    pub fn validate_layout(&self, disk_sizes: &[usize], cd_position: Option<ZipPosition>) -> Result<(), LayoutError> {
+        let global_offset = |pos: ZipPosition| -> Option<u64> {
+            if pos.disk >= disk_sizes.len() || pos.offset >= disk_sizes[pos.disk] {
+                return None;
+            }
+
+            Some(disk_sizes[..pos.disk].iter().sum::<usize>() as u64 + pos.offset as u64)
+        };
+
+        let total_size = disk_sizes.iter().sum::<usize>() as u64;
+        let cd_start = cd_position.and_then(global_offset);
+
+        let mut issues = Vec::new();
+
+        // Index and end of the entry which extends the furthest so far
+        let mut furthest: Option<(usize, u64)> = None;
+
+        for (i, cdfh) in self.headers.iter().enumerate() {
+            let Some(start) = global_offset(cdfh.header_position()) else {
+                issues.push(LayoutIssue::OutOfBounds(i));
+                continue;
+            };
+
+            let end = start
+                .saturating_add((4 + LFH_CONSTANT_SIZE + cdfh.filename_raw.len()) as u64)
+                .saturating_add(cdfh.compressed_size);
+
+            if let Some((j, furthest_end)) = furthest {
+                if start < furthest_end {
+                    issues.push(LayoutIssue::Overlap(j, i));
+                }
+            }
+
+            if cd_start.is_some_and(|cd_start| end > cd_start) {
+                issues.push(LayoutIssue::OverlapsCentralDirectory(i));
+            }
+
+            if end > total_size {
+                issues.push(LayoutIssue::OutOfBounds(i));
+            }
+
+            if furthest.is_none_or(|(_, furthest_end)| end > furthest_end) {
+                furthest = Some((i, end));
+            }
+        }
+
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(LayoutError(issues))
+        }
+    }
 }
 
 pub const CDFH_SIGNATURE: u32 = 0x02014B50;
@@ -120,6 +222,10 @@ pub struct CentralDirectoryFileHeader {
     pub uncompressed_size: u64,
 
     pub filename: String,
+    pub filename_source: TextSource,
+
+    /// The original bytes of the filename, before any decoding
+    pub filename_raw: Vec<u8>,
     
     pub extra_fields: Vec<FileHeaderExtraField>,
 
@@ -133,6 +239,13 @@ pub struct CentralDirectoryFileHeader {
     #[cfg(feature = "zip-comments")]
     pub comment: String,
 
+    #[cfg(feature = "zip-comments")]
+    pub comment_source: TextSource,
+
+    /// The original bytes of the comment, before any decoding
+    #[cfg(feature = "zip-comments")]
+    pub comment_raw: Vec<u8>,
+
     pub header_size: usize
 }
 
@@ -175,17 +288,19 @@ impl CentralDirectoryFileHeader {
 
         let filename_start = CDFH_CONSTANT_SIZE;
         let filename_end = filename_start + filename_length;
-        let filename = String::from_utf8_lossy(&data[filename_start..filename_end]).to_string();
 
         let extra_fields_start = filename_end;
         let extra_fields_end = extra_fields_start + extra_fields_length;
-        let Some(extra_fields) = FileHeaderExtraField::read_extra_fields(&data[extra_fields_start..extra_fields_end]) else {
-            return None;
-        };
+        let extra_fields = FileHeaderExtraField::read_extra_fields(&data[extra_fields_start..extra_fields_end])?;
 
         let comment_start = extra_fields_end;
         let comment_end = comment_start + comment_length;
 
+        let (filename, filename_source) = decode_text(&data[filename_start..filename_end], flag, &extra_fields, UNICODE_PATH_EXTRA_FIELD_ID);
+
+        #[cfg(feature = "zip-comments")]
+        let (comment, comment_source) = decode_text(&data[comment_start..comment_end], flag, &extra_fields, UNICODE_COMMENT_EXTRA_FIELD_ID);
+
         let original_zip64_data = Zip64OriginalData {
             uncompressed_size,
             compressed_size,
@@ -193,14 +308,12 @@ impl CentralDirectoryFileHeader {
             disk_number
         };
 
-        let Some(Zip64ProcessedData {
+        let Zip64ProcessedData {
             uncompressed_size,
             compressed_size,
             local_header_offset,
             disk_number
-        }) = original_zip64_data.process(&extra_fields) else {
-            return None;
-        };
+        } = original_zip64_data.process(&extra_fields)?;
 
         Some(Self {
             version_made_by,
@@ -213,6 +326,8 @@ impl CentralDirectoryFileHeader {
             compressed_size,
             uncompressed_size,
             filename,
+            filename_source,
+            filename_raw: data[filename_start..filename_end].to_owned(),
             extra_fields,
             disk_number,
             internal_attributes,
@@ -220,14 +335,103 @@ impl CentralDirectoryFileHeader {
             local_header_offset,
 
             #[cfg(feature = "zip-comments")]
-            comment: String::from_utf8_lossy(&data[comment_start..comment_end]).to_string(),
+            comment,
+
+            #[cfg(feature = "zip-comments")]
+            comment_source,
+
+            #[cfg(feature = "zip-comments")]
+            comment_raw: data[comment_start..comment_end].to_owned(),
 
             header_size: comment_end
         })
     }
 
+    /// Returns the filename as a relative path which can be safely joined with
+    /// an output directory. See [sanitize_path] for details
+    pub fn sanitized_path(&self) -> Result<PathBuf, PathSanitizationError> {
+        sanitize_path(&self.filename)
+    }
+
+    /// Returns whether this file is a directory, according to
+    /// either the filename or the external attributes
     pub fn is_directory(&self) -> bool {
-        self.filename.ends_with('/')
+        self.filename.ends_with('/') || self.entry_kind() == EntryKind::Directory
+    }
+
+    /// Returns the external attributes of this file, interpreted
+    /// according to the system which created it
+    pub fn attributes(&self) -> FileAttributes {
+        FileAttributes::new(self.version_made_by, self.external_attributes)
+    }
+
+    /// Returns the type of this file. If the external attributes don't determine it,
+    /// names ending with a slash are directories, and everything else is a file
+    pub fn entry_kind(&self) -> EntryKind {
+        self.attributes()
+            .kind()
+            .unwrap_or(if self.filename.ends_with('/') {
+                EntryKind::Directory
+            } else {
+                EntryKind::File
+            })
+    }
+
+    /// Returns whether this file is encrypted
+    pub fn is_encrypted(&self) -> bool {
+        self.flag & FLAG_ENCRYPTED != 0
+    }
+
+    /// Returns whether this header contains a ZIP64 extra field
+    pub fn is_zip64(&self) -> bool {
+        self.extra_fields.iter().any(|f| f.id == ZIP64_EXTRA_FIELD_ID)
+    }
+
+    /// Returns the unsupported feature used by this file, if there is one
+    pub fn unsupported_feature(&self) -> Option<UnsupportedFeature> {
+        unsupported_feature(self.flag)
+    }
+
+    /// Parses all extra fields of this file using the provided registry.
+    /// Use [ExtraFieldRegistry::default] to only parse the well-known fields
+    pub fn parsed_extra_fields(&self, registry: &ExtraFieldRegistry) -> Vec<ExtraField> {
+        registry.parse_all(&self.extra_fields, true)
+    }
+
+    /// Returns the WinZip AES extra field of this file, if there is one.
+    /// If there is, the actual compression method is stored in it
+    pub fn aes_extra_field(&self) -> Option<AesExtraField> {
+        AesExtraField::from_fields(&self.extra_fields)
+    }
+
+    /// Returns the extended timestamp extra field of this file, if there is one
+    pub fn extended_timestamp(&self) -> Option<ExtendedTimestamp> {
+        ExtendedTimestamp::from_fields(&self.extra_fields, true)
+    }
+
+    /// Returns the timestamps from the NTFS extra field of this file, if there is one
+    pub fn ntfs_timestamps(&self) -> Option<NtfsTimestamps> {
+        NtfsTimestamps::from_fields(&self.extra_fields)
+    }
+
+    /// Returns the owner of this file from the Info-ZIP or PKWARE Unix extra field, if there is one
+    pub fn unix_ownership(&self) -> Option<UnixOwnership> {
+        UnixOwnership::from_fields(&self.extra_fields)
+    }
+
+    /// Returns the DOS modification date and time of this file, or None if it is invalid
+    pub fn date_time(&self) -> Option<ZipDateTime> {
+        ZipDateTime::from_dos(self.mod_date, self.mod_time)
+    }
+
+    /// Returns the modification time of this file. It is taken from the NTFS or
+    /// the extended timestamp extra field if there is one, otherwise the DOS time is
+    /// used, interpreted in the specified time zone (the extra fields are always UTC)
+    pub fn modification_time(&self, time_zone: ZipTimeZone) -> Option<SystemTime> {
+        self.ntfs_timestamps()
+            .map(|t| t.modification_time)
+            .or_else(|| self.extended_timestamp().and_then(|t| t.modification_time))
+            .or_else(|| self.date_time().map(|t| t.to_system_time(time_zone)))
     }
 
     /// Returns the [ZipPosition] of the LFH corresponding to this CDFH
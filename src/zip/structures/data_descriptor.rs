@@ -0,0 +1,101 @@
+use std::io::Cursor;
+
+use byteorder::{ReadBytesExt, LittleEndian};
+
+pub const DATA_DESCRIPTOR_SIGNATURE: u32 = 0x08074b50;
+pub const DATA_DESCRIPTOR_CONSTANT_SIZE: usize = 12;
+pub const DATA_DESCRIPTOR64_CONSTANT_SIZE: usize = 20;
+
+/// Represents the result of reading a ZIP data descriptor, which
+/// follows the file data if bit 3 of the general purpose flag is set
+#[derive(Debug, Clone)]
+pub struct DataDescriptor {
+    pub crc32: u32,
+
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+
+    pub descriptor_size: usize
+}
+
+impl DataDescriptor {
+    /// Attempts to read a data descriptor from the provided byte buffer.
+    /// The optional signature is detected automatically, "zip64" determines 
+    /// whether the sizes are 8 bytes long. Returns None if there isn't enough data
+    pub fn from_bytes(data: impl AsRef<[u8]>, zip64: bool) -> Option<Self> {
+        let data = data.as_ref();
+        if data.len() < 4 {
+            return None;
+        }
+
+        Self::from_layout(data, has_signature(data), zip64)
+    }
+
+    /// Attempts to read a data descriptor which is expected to contain the provided
+    /// CRC-32 and sizes (usually taken from the central directory). Since the signature
+    /// is optional and some writers use 8 byte sizes without a ZIP64 extra field, every
+    /// layout is tried, starting with the one [DataDescriptor::from_bytes] would pick.
+    /// If none of them match, that layout is returned, which can be checked with
+    /// [DataDescriptor::matches]. Returns None if there isn't enough data
+    pub fn from_bytes_matching(data: impl AsRef<[u8]>, zip64: bool, crc32: u32, compressed_size: u64, uncompressed_size: u64) -> Option<Self> {
+        let data = data.as_ref();
+        if data.len() < 4 {
+            return None;
+        }
+
+        // A descriptor without a signature may have a CRC-32 equal to it
+        let signatures: &[bool] = if has_signature(data) { &[true, false] } else { &[false] };
+
+        for &signature in signatures {
+            for zip64 in [zip64, !zip64] {
+                let descriptor = Self::from_layout(data, signature, zip64)?;
+                if descriptor.matches(crc32, compressed_size, uncompressed_size) {
+                    return Some(descriptor);
+                }
+            }
+        }
+
+        Self::from_layout(data, signatures[0], zip64)
+    }
+
+    /// Returns whether this data descriptor contains the provided CRC-32 and sizes
+    pub fn matches(&self, crc32: u32, compressed_size: u64, uncompressed_size: u64) -> bool {
+        self.crc32 == crc32 && self.compressed_size == compressed_size && self.uncompressed_size == uncompressed_size
+    }
+
+    fn from_layout(data: &[u8], signature: bool, zip64: bool) -> Option<Self> {
+        let signature_size = if signature { 4 } else { 0 };
+
+        let constant_size = if zip64 { DATA_DESCRIPTOR64_CONSTANT_SIZE } else { DATA_DESCRIPTOR_CONSTANT_SIZE };
+        if data.len() < signature_size + constant_size {
+            return None;
+        }
+
+        let mut cursor = Cursor::new(&data[signature_size..]);
+
+        let crc32 = cursor.read_u32::<LittleEndian>().unwrap();
+        let (compressed_size, uncompressed_size) = if zip64 {
+            (
+                cursor.read_u64::<LittleEndian>().unwrap(),
+                cursor.read_u64::<LittleEndian>().unwrap()
+            )
+        } else {
+            (
+                cursor.read_u32::<LittleEndian>().unwrap() as u64,
+                cursor.read_u32::<LittleEndian>().unwrap() as u64
+            )
+        };
+
+        Some(Self {
+            crc32,
+            compressed_size,
+            uncompressed_size,
+
+            descriptor_size: signature_size + constant_size
+        })
+    }
+}
+
+fn has_signature(data: &[u8]) -> bool {
+    u32::from_le_bytes(data[..4].try_into().unwrap()) == DATA_DESCRIPTOR_SIGNATURE
+}
@@ -0,0 +1,205 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Determines how a [ZipDateTime], which does not store a time zone, is interpreted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ZipTimeZone {
+    Utc,
+
+    /// A fixed offset from UTC in seconds. DOS times are usually stored in the local
+    /// time of the machine which created the archive, so this should be set to
+    /// its UTC offset (if known) to obtain the correct time
+    FixedOffset(i32),
+
+    /// The local time zone of this machine. The UTC offset is resolved by the OS
+    /// separately for every date, so daylight saving time is taken into account.
+    /// Falls back to UTC if the OS can't convert the time, or on platforms other
+    /// than Unix and Windows
+    #[cfg(feature = "zip-local-time")]
+    Local
+}
+
+/// A validated date and time, as stored in ZIP file headers (DOS format).
+/// The year is in range 1980-2107 and the seconds are always even
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ZipDateTime {
+    year: u16,
+    month: u8,
+    day: u8,
+    hour: u8,
+    minute: u8,
+    second: u8
+}
+
+impl std::fmt::Display for ZipDateTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04}-{:02}-{:02} {:02}:{:02}:{:02}", self.year, self.month, self.day, self.hour, self.minute, self.second)
+    }
+}
+
+impl ZipDateTime {
+    /// Creates a new ZipDateTime. Returns None if any of the components are 
+    /// out of range, or if the date and time can't be represented in DOS format
+    pub fn new(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> Option<Self> {
+        if !(1980..=2107).contains(&year) || !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+            return None;
+        }
+
+        if hour > 23 || minute > 59 || second > 59 || !second.is_multiple_of(2) {
+            return None;
+        }
+
+        Some(Self {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second
+        })
+    }
+
+    /// Decodes a DOS date and time pair. Returns None if they are invalid, which
+    /// includes the all-zero date some writers emit when the time is unknown
+    pub fn from_dos(date: u16, time: u16) -> Option<Self> {
+        Self::new(
+            1980 + (date >> 9),
+            ((date >> 5) & 0x0F) as u8,
+            (date & 0x1F) as u8,
+            (time >> 11) as u8,
+            ((time >> 5) & 0x3F) as u8,
+            ((time & 0x1F) * 2) as u8
+        )
+    }
+
+    /// Encodes this ZipDateTime as a DOS date and time pair
+    pub fn to_dos(&self) -> (u16, u16) {
+        let date = ((self.year - 1980) << 9) | ((self.month as u16) << 5) | self.day as u16;
+        let time = ((self.hour as u16) << 11) | ((self.minute as u16) << 5) | (self.second as u16 / 2);
+
+        (date, time)
+    }
+
+    /// Converts this ZipDateTime to a [SystemTime], interpreting it in the specified time zone
+    pub fn to_system_time(&self, time_zone: ZipTimeZone) -> SystemTime {
+        let seconds = civil_to_unix_time(self.year as i64, self.month as i64, self.day as i64, self.hour as i64, self.minute as i64, self.second as i64);
+        let offset = match time_zone {
+            ZipTimeZone::Utc => 0,
+            ZipTimeZone::FixedOffset(offset) => offset as i64,
+
+            #[cfg(feature = "zip-local-time")]
+            ZipTimeZone::Local => local_offset(self, seconds).unwrap_or(0)
+        };
+
+        unix_time_to_system_time(seconds - offset)
+    }
+
+    pub fn year(&self) -> u16 {
+        self.year
+    }
+
+    pub fn month(&self) -> u8 {
+        self.month
+    }
+
+    pub fn day(&self) -> u8 {
+        self.day
+    }
+
+    pub fn hour(&self) -> u8 {
+        self.hour
+    }
+
+    pub fn minute(&self) -> u8 {
+        self.minute
+    }
+
+    pub fn second(&self) -> u8 {
+        self.second
+    }
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31
+    }
+}
+
+/// Converts a date and time in UTC to seconds since the Unix epoch
+fn civil_to_unix_time(year: i64, month: i64, day: i64, hour: i64, minute: i64, second: i64) -> i64 {
+    // Days since 1970-01-01, see http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+
+    days * 86400 + hour * 3600 + minute * 60 + second
+}
+
+/// Returns the UTC offset of the local time zone at the specified local date and
+/// time. "seconds" is the same date and time converted as if it was in UTC
+#[cfg(all(feature = "zip-local-time", unix))]
+fn local_offset(date_time: &ZipDateTime, seconds: i64) -> Option<i64> {
+    // SAFETY: tm is a plain C struct, for which all zeroes is a valid value
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    tm.tm_year = date_time.year as i32 - 1900;
+    tm.tm_mon = date_time.month as i32 - 1;
+    tm.tm_mday = date_time.day as i32;
+    tm.tm_hour = date_time.hour as i32;
+    tm.tm_min = date_time.minute as i32;
+    tm.tm_sec = date_time.second as i32;
+
+    // Let the OS determine whether daylight saving time is in effect
+    tm.tm_isdst = -1;
+
+    // SAFETY: tm is initialized and mktime doesn't keep the pointer
+    let utc = unsafe { libc::mktime(&mut tm) };
+    (utc != -1).then(|| seconds - utc as i64)
+}
+
+/// Returns the UTC offset of the local time zone at the specified local date and
+/// time. "seconds" is the same date and time converted as if it was in UTC
+#[cfg(all(feature = "zip-local-time", windows))]
+fn local_offset(date_time: &ZipDateTime, seconds: i64) -> Option<i64> {
+    use windows_sys::Win32::{Foundation::SYSTEMTIME, System::Time::TzSpecificLocalTimeToSystemTime};
+
+    let local = SYSTEMTIME {
+        wYear: date_time.year,
+        wMonth: date_time.month as u16,
+        wDayOfWeek: 0,
+        wDay: date_time.day as u16,
+        wHour: date_time.hour as u16,
+        wMinute: date_time.minute as u16,
+        wSecond: date_time.second as u16,
+        wMilliseconds: 0
+    };
+    let mut utc = local;
+
+    // SAFETY: both pointers are valid for the duration of the call,
+    // and a null time zone means the currently active one
+    if unsafe { TzSpecificLocalTimeToSystemTime(std::ptr::null(), &local, &mut utc) } == 0 {
+        return None;
+    }
+
+    let utc = civil_to_unix_time(utc.wYear as i64, utc.wMonth as i64, utc.wDay as i64, utc.wHour as i64, utc.wMinute as i64, utc.wSecond as i64);
+    Some(seconds - utc)
+}
+
+#[cfg(all(feature = "zip-local-time", not(any(unix, windows))))]
+fn local_offset(_date_time: &ZipDateTime, _seconds: i64) -> Option<i64> {
+    None
+}
+
+/// Converts seconds since the Unix epoch to a [SystemTime]
+pub(crate) fn unix_time_to_system_time(seconds: i64) -> SystemTime {
+    if seconds >= 0 {
+        UNIX_EPOCH + Duration::from_secs(seconds as u64)
+    } else {
+        UNIX_EPOCH - Duration::from_secs(seconds.unsigned_abs())
+    }
+}
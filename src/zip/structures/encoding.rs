@@ -0,0 +1,39 @@
+/// Characters 0x80-0xFF of IBM code page 437. The lower half is identical to ASCII
+const CP437_UPPER_HALF: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{A0}'
+];
+
+/// Decodes bytes encoded with IBM code page 437. Every
+/// byte maps to a character, so this never fails
+pub fn decode_cp437(data: impl AsRef<[u8]>) -> String {
+    data.as_ref()
+        .iter()
+        .map(|b| if *b < 0x80 {
+            *b as char
+        } else {
+            CP437_UPPER_HALF[(*b - 0x80) as usize]
+        })
+        .collect()
+}
+
+/// Decodes a filename or comment from a file header. If "is_utf8" is set (bit 11 of
+/// the general purpose flag), it is decoded as UTF-8, falling back to CP437 if it is
+/// not valid UTF-8. Otherwise, it is decoded as CP437, as required by the specification
+pub fn decode_header_text(data: impl AsRef<[u8]>, is_utf8: bool) -> String {
+    let data = data.as_ref();
+
+    if is_utf8 {
+        if let Ok(text) = std::str::from_utf8(data) {
+            return text.to_owned();
+        }
+    }
+
+    decode_cp437(data)
+}
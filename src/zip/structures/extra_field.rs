@@ -0,0 +1,159 @@
+use std::{any::Any, collections::HashMap, io::Cursor};
+
+use byteorder::{ReadBytesExt, LittleEndian};
+
+use super::file_header::{FileHeaderExtraField, AesExtraField, ExtendedTimestamp, NtfsTimestamps, UnixOwnership, UnicodeExtraField, ZIP64_EXTRA_FIELD_ID, AES_EXTRA_FIELD_ID, EXTENDED_TIMESTAMP_EXTRA_FIELD_ID, NTFS_EXTRA_FIELD_ID, INFOZIP_UNIX_EXTRA_FIELD_ID, PKWARE_UNIX_EXTRA_FIELD_ID, UNICODE_PATH_EXTRA_FIELD_ID, UNICODE_COMMENT_EXTRA_FIELD_ID};
+
+pub const ANDROID_ALIGNMENT_EXTRA_FIELD_ID: u16 = 0xD935;
+
+/// Contains the raw values of a ZIP64 extra field. Which header fields they
+/// replace depends on which of them are set to their maximum value, so
+/// they are already applied to the header sizes and offsets
+#[derive(Debug, Clone)]
+pub struct Zip64ExtraField {
+    pub values: Vec<u64>,
+    pub disk_number: Option<u32>
+}
+
+impl Zip64ExtraField {
+    /// Reads a ZIP64 extra field. A trailing 4 byte value is interpreted as the disk number
+    pub fn from_field(field: &FileHeaderExtraField) -> Self {
+        let mut cursor = Cursor::new(&field.data);
+
+        let values = (0..(field.data.len() / 8))
+            .map(|_| cursor.read_u64::<LittleEndian>().unwrap())
+            .collect();
+        let disk_number = cursor.read_u32::<LittleEndian>().ok();
+
+        Self {
+            values,
+            disk_number
+        }
+    }
+}
+
+/// Contains the data of an Android (zipalign) alignment extra field, which
+/// pads the local file header so that the file data is aligned
+#[derive(Debug, Clone)]
+pub struct AndroidAlignment {
+    pub alignment: u16,
+    pub padding: usize
+}
+
+impl AndroidAlignment {
+    /// Attempts to read an Android alignment extra field. Returns None if it is malformed
+    pub fn from_field(field: &FileHeaderExtraField) -> Option<Self> {
+        if field.data.len() < 2 {
+            return None;
+        }
+
+        Some(Self {
+            alignment: u16::from_le_bytes(field.data[..2].try_into().unwrap()),
+            padding: field.data.len() - 2
+        })
+    }
+}
+
+/// Represents a parsed ZIP file header extra field. See [ExtraFieldRegistry::parse]
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ExtraField {
+    Zip64(Zip64ExtraField),
+    Ntfs(NtfsTimestamps),
+    ExtendedTimestamp(ExtendedTimestamp),
+    InfoZipUnix(UnixOwnership),
+    PkwareUnix(UnixOwnership),
+    UnicodePath(UnicodeExtraField),
+    UnicodeComment(UnicodeExtraField),
+    Aes(AesExtraField),
+    AndroidAlignment(AndroidAlignment),
+
+    /// A field parsed by a parser registered in an [ExtraFieldRegistry]
+    Custom(u16, Box<dyn Any + Send + Sync>),
+
+    /// A field with an unknown id, or a malformed one
+    Unknown(FileHeaderExtraField)
+}
+
+impl ExtraField {
+    /// Parses a field with one of the well-known ids. "is_central" determines
+    /// whether the field comes from a central directory file header
+    /// 
+    /// Returns [ExtraField::Unknown] if the id is not known, or if the field is malformed
+    pub fn from_raw(field: &FileHeaderExtraField, is_central: bool) -> Self {
+        let parsed = match field.id {
+            ZIP64_EXTRA_FIELD_ID => Some(Self::Zip64(Zip64ExtraField::from_field(field))),
+            NTFS_EXTRA_FIELD_ID => NtfsTimestamps::from_field(field).map(Self::Ntfs),
+            EXTENDED_TIMESTAMP_EXTRA_FIELD_ID => ExtendedTimestamp::from_field(field, is_central).map(Self::ExtendedTimestamp),
+            INFOZIP_UNIX_EXTRA_FIELD_ID => UnixOwnership::from_infozip_field(field).map(Self::InfoZipUnix),
+            PKWARE_UNIX_EXTRA_FIELD_ID => UnixOwnership::from_pkware_field(field).map(Self::PkwareUnix),
+            UNICODE_PATH_EXTRA_FIELD_ID => UnicodeExtraField::from_field(field).map(Self::UnicodePath),
+            UNICODE_COMMENT_EXTRA_FIELD_ID => UnicodeExtraField::from_field(field).map(Self::UnicodeComment),
+            AES_EXTRA_FIELD_ID => AesExtraField::from_field(field).map(Self::Aes),
+            ANDROID_ALIGNMENT_EXTRA_FIELD_ID => AndroidAlignment::from_field(field).map(Self::AndroidAlignment),
+
+            _ => None
+        };
+
+        parsed.unwrap_or_else(|| Self::Unknown(field.clone()))
+    }
+
+    /// Returns a reference to the value of an [ExtraField::Custom] field
+    /// if it has the specified type
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        match self {
+            Self::Custom(_, value) => value.downcast_ref(),
+            _ => None
+        }
+    }
+}
+
+type ExtraFieldParser = dyn Fn(&[u8]) -> Option<Box<dyn Any + Send + Sync>> + Send + Sync;
+
+/// A set of parsers for application-specific extra fields
+#[derive(Default)]
+pub struct ExtraFieldRegistry {
+    parsers: HashMap<u16, Box<ExtraFieldParser>>
+}
+
+impl std::fmt::Debug for ExtraFieldRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ExtraFieldRegistry")
+            .field("ids", &self.parsers.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl ExtraFieldRegistry {
+    /// Creates an empty ExtraFieldRegistry, which only parses the well-known fields
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a parser for fields with the specified id. The parser receives the field
+    /// data and may return None if it is malformed. Registered parsers take precedence
+    /// over the built-in ones, and replace previously registered parsers for the same id
+    pub fn register<T: Any + Send + Sync>(&mut self, id: u16, parser: impl Fn(&[u8]) -> Option<T> + Send + Sync + 'static) {
+        self.parsers.insert(id, Box::new(move |data| {
+            parser(data).map(|v| Box::new(v) as Box<dyn Any + Send + Sync>)
+        }));
+    }
+
+    /// Parses a field using a registered parser if there is one for its id,
+    /// and [ExtraField::from_raw] otherwise
+    pub fn parse(&self, field: &FileHeaderExtraField, is_central: bool) -> ExtraField {
+        match self.parsers.get(&field.id).map(|p| (p)(&field.data)) {
+            Some(Some(value)) => ExtraField::Custom(field.id, value),
+            Some(None) => ExtraField::Unknown(field.clone()),
+            None => ExtraField::from_raw(field, is_central)
+        }
+    }
+
+    /// Parses all fields, see [ExtraFieldRegistry::parse]
+    pub fn parse_all(&self, fields: impl AsRef<[FileHeaderExtraField]>, is_central: bool) -> Vec<ExtraField> {
+        fields.as_ref()
+            .iter()
+            .map(|f| self.parse(f, is_central))
+            .collect()
+    }
+}
@@ -1,7 +1,9 @@
-use std::io::Cursor;
+use std::{io::Cursor, time::{Duration, SystemTime, UNIX_EPOCH}};
 
 use byteorder::{ReadBytesExt, LittleEndian};
 
+use super::{encoding::decode_header_text, date_time::unix_time_to_system_time};
+
 /// Contains raw ZIP file header extra field data
 #[derive(Debug, Clone)]
 pub struct FileHeaderExtraField {
@@ -41,9 +43,7 @@ impl FileHeaderExtraField {
         let mut extra_fileds = Vec::new();
         let mut offset = 0;
         while offset < data.len() {
-            let Some(field) = FileHeaderExtraField::from_bytes(&data[offset..]) else {
-                return None;
-            };
+            let field = FileHeaderExtraField::from_bytes(&data[offset..])?;
 
             offset += field.size();
             extra_fileds.push(field);
@@ -58,6 +58,42 @@ impl FileHeaderExtraField {
     }
 }
 
+/// Set if the file is encrypted
+pub const FLAG_ENCRYPTED: u16 = 1 << 0;
+
+/// Set if the filename and comment are encoded with UTF-8 (language encoding flag)
+pub const FLAG_UTF8: u16 = 1 << 11;
+
+/// Set if the sizes and CRC-32 are stored in a data descriptor after the file data
+pub const FLAG_DATA_DESCRIPTOR: u16 = 1 << 3;
+
+/// Set if the file is encrypted with PKWARE strong encryption
+pub const FLAG_STRONG_ENCRYPTION: u16 = 1 << 6;
+
+/// Set if the central directory is encrypted, in which case
+/// some of the LFH fields are masked
+pub const FLAG_MASKED_LOCAL_HEADER: u16 = 1 << 13;
+
+/// A ZIP feature which is recognized, but not supported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum UnsupportedFeature {
+    StrongEncryption,
+    CentralDirectoryEncryption,
+    CentralDirectoryCompression
+}
+
+/// Returns the unsupported feature indicated by the general purpose flag, if there is one
+pub(crate) fn unsupported_feature(flag: u16) -> Option<UnsupportedFeature> {
+    if flag & FLAG_MASKED_LOCAL_HEADER != 0 {
+        Some(UnsupportedFeature::CentralDirectoryEncryption)
+    } else if flag & FLAG_STRONG_ENCRYPTION != 0 {
+        Some(UnsupportedFeature::StrongEncryption)
+    } else {
+        None
+    }
+}
+
 pub const ZIP64_EXTRA_FIELD_ID: u16 = 0x0001;
 
 #[derive(Debug, Default)]
@@ -149,3 +185,358 @@ impl Zip64OriginalData {
         }
     }
 }
+
+pub const AES_EXTRA_FIELD_ID: u16 = 0x9901;
+pub const AES_EXTRA_FIELD_SIZE: usize = 7;
+
+/// Contains the data of a WinZip AES extra field
+#[derive(Debug, Clone)]
+pub struct AesExtraField {
+    /// 1 for AE-1, 2 for AE-2. The CRC-32 is not stored for AE-2
+    pub vendor_version: u16,
+
+    /// 1, 2 or 3 for 128, 192 or 256 bit keys respectively
+    pub strength: u8,
+
+    /// The actual compression method of the file
+    pub compression_method: u16
+}
+
+impl AesExtraField {
+    /// Attempts to find and read a WinZip AES extra field in the provided fields.
+    /// Returns None if there isn't one, or if it is malformed
+    pub fn from_fields(fields: impl AsRef<[FileHeaderExtraField]>) -> Option<Self> {
+        fields.as_ref()
+            .iter()
+            .find(|f| f.id == AES_EXTRA_FIELD_ID)
+            .and_then(Self::from_field)
+    }
+
+    /// Attempts to read a WinZip AES extra field. Returns None if it is malformed
+    pub fn from_field(field: &FileHeaderExtraField) -> Option<Self> {
+        if field.data.len() < AES_EXTRA_FIELD_SIZE || &field.data[2..4] != b"AE" {
+            return None;
+        }
+
+        let mut cursor = Cursor::new(&field.data);
+
+        let vendor_version = cursor.read_u16::<LittleEndian>().unwrap();
+        cursor.set_position(4);
+        let strength = cursor.read_u8().unwrap();
+        let compression_method = cursor.read_u16::<LittleEndian>().unwrap();
+
+        Some(Self {
+            vendor_version,
+            strength,
+            compression_method
+        })
+    }
+
+    /// Returns the key size in bytes, or None if the strength is invalid
+    pub fn key_size(&self) -> Option<usize> {
+        match self.strength {
+            1 => Some(16),
+            2 => Some(24),
+            3 => Some(32),
+            _ => None
+        }
+    }
+
+    /// Returns the salt size in bytes, or None if the strength is invalid
+    pub fn salt_size(&self) -> Option<usize> {
+        self.key_size().map(|s| s / 2)
+    }
+
+    /// Returns whether the CRC-32 of the file is stored
+    pub fn has_crc32(&self) -> bool {
+        self.vendor_version != 2
+    }
+}
+
+pub const EXTENDED_TIMESTAMP_EXTRA_FIELD_ID: u16 = 0x5455;
+
+/// Contains the data of an Info-ZIP extended timestamp extra field.
+/// The central directory variant only contains the modification time
+#[derive(Debug, Clone, Default)]
+pub struct ExtendedTimestamp {
+    pub modification_time: Option<SystemTime>,
+    pub access_time: Option<SystemTime>,
+    pub creation_time: Option<SystemTime>
+}
+
+impl ExtendedTimestamp {
+    /// Attempts to find and read an extended timestamp extra field in the provided fields.
+    /// "is_central" determines whether the fields come from a central directory file header.
+    /// Returns None if there isn't one, or if it is malformed
+    pub fn from_fields(fields: impl AsRef<[FileHeaderExtraField]>, is_central: bool) -> Option<Self> {
+        fields.as_ref()
+            .iter()
+            .find(|f| f.id == EXTENDED_TIMESTAMP_EXTRA_FIELD_ID)
+            .and_then(|f| Self::from_field(f, is_central))
+    }
+
+    /// Attempts to read an extended timestamp extra field. "is_central" determines whether
+    /// it comes from a central directory file header. Returns None if it is malformed
+    pub fn from_field(field: &FileHeaderExtraField, is_central: bool) -> Option<Self> {
+        if field.data.is_empty() {
+            return None;
+        }
+
+        let mut cursor = Cursor::new(&field.data);
+        let flags = cursor.read_u8().unwrap();
+
+        // The flags describe the times in the local variant, so they may
+        // be set for times which are not actually present in the central one
+        let time_count = if is_central { 1 } else { 3 };
+
+        let mut times = [None; 3];
+        for (i, time) in times.iter_mut().enumerate().take(time_count) {
+            if flags & (1 << i) == 0 {
+                continue;
+            }
+
+            let Ok(seconds) = cursor.read_i32::<LittleEndian>() else {
+                break;
+            };
+
+            *time = Some(unix_time_to_system_time(seconds as i64));
+        }
+
+        let [modification_time, access_time, creation_time] = times;
+
+        Some(Self {
+            modification_time,
+            access_time,
+            creation_time
+        })
+    }
+}
+
+pub const NTFS_EXTRA_FIELD_ID: u16 = 0x000A;
+pub const NTFS_TIMESTAMPS_TAG: u16 = 0x0001;
+pub const NTFS_TIMESTAMPS_SIZE: usize = 24;
+
+/// Contains the timestamps from an NTFS extra field, which have a precision of 100ns
+#[derive(Debug, Clone)]
+pub struct NtfsTimestamps {
+    pub modification_time: SystemTime,
+    pub access_time: SystemTime,
+    pub creation_time: SystemTime
+}
+
+impl NtfsTimestamps {
+    /// Attempts to find an NTFS extra field in the provided fields and read the timestamp
+    /// attribute from it. Returns None if there isn't one, or if it is malformed
+    pub fn from_fields(fields: impl AsRef<[FileHeaderExtraField]>) -> Option<Self> {
+        fields.as_ref()
+            .iter()
+            .find(|f| f.id == NTFS_EXTRA_FIELD_ID)
+            .and_then(Self::from_field)
+    }
+
+    /// Attempts to read the timestamp attribute from an NTFS extra field.
+    /// Returns None if there isn't one, or if the field is malformed
+    pub fn from_field(field: &FileHeaderExtraField) -> Option<Self> {
+
+        // Skip the reserved bytes
+        let mut offset = 4;
+        while offset + 4 <= field.data.len() {
+            let mut cursor = Cursor::new(&field.data[offset..]);
+
+            let tag = cursor.read_u16::<LittleEndian>().unwrap();
+            let size = cursor.read_u16::<LittleEndian>().unwrap() as usize;
+
+            if tag == NTFS_TIMESTAMPS_TAG {
+                if size < NTFS_TIMESTAMPS_SIZE || field.data.len() < offset + 4 + size {
+                    return None;
+                }
+
+                let modification_time = cursor.read_u64::<LittleEndian>().unwrap();
+                let access_time = cursor.read_u64::<LittleEndian>().unwrap();
+                let creation_time = cursor.read_u64::<LittleEndian>().unwrap();
+
+                return Some(Self {
+                    modification_time: filetime_to_system_time(modification_time),
+                    access_time: filetime_to_system_time(access_time),
+                    creation_time: filetime_to_system_time(creation_time)
+                });
+            }
+
+            offset += 4 + size;
+        }
+
+        None
+    }
+}
+
+/// Converts a Windows FILETIME (100ns intervals since 1601-01-01) to a [SystemTime]
+fn filetime_to_system_time(filetime: u64) -> SystemTime {
+    const UNIX_EPOCH_FILETIME: u64 = 116444736000000000;
+
+    let to_duration = |intervals: u64| Duration::new(
+        intervals / 10_000_000, 
+        (intervals % 10_000_000) as u32 * 100
+    );
+
+    if filetime >= UNIX_EPOCH_FILETIME {
+        UNIX_EPOCH + to_duration(filetime - UNIX_EPOCH_FILETIME)
+    } else {
+        UNIX_EPOCH - to_duration(UNIX_EPOCH_FILETIME - filetime)
+    }
+}
+
+pub const INFOZIP_UNIX_EXTRA_FIELD_ID: u16 = 0x7875;
+pub const PKWARE_UNIX_EXTRA_FIELD_ID: u16 = 0x000D;
+pub const PKWARE_UNIX_EXTRA_FIELD_CONSTANT_SIZE: usize = 12;
+
+/// Contains the owner of a file from an Info-ZIP or PKWARE Unix extra field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnixOwnership {
+    pub uid: u32,
+    pub gid: u32
+}
+
+impl UnixOwnership {
+    /// Attempts to find and read an Info-ZIP "new Unix" extra field in the provided fields,
+    /// falling back to a PKWARE Unix extra field. Returns None if there isn't one, or if it is malformed
+    pub fn from_fields(fields: impl AsRef<[FileHeaderExtraField]>) -> Option<Self> {
+        let fields = fields.as_ref();
+
+        fields.iter()
+            .find(|f| f.id == INFOZIP_UNIX_EXTRA_FIELD_ID)
+            .and_then(Self::from_infozip_field)
+            .or_else(|| {
+                fields.iter()
+                    .find(|f| f.id == PKWARE_UNIX_EXTRA_FIELD_ID)
+                    .and_then(Self::from_pkware_field)
+            })
+    }
+
+    /// Attempts to read an Info-ZIP "new Unix" (0x7875) extra field, in which
+    /// the UID and GID have variable sizes. Returns None if it is malformed,
+    /// or if either of the IDs does not fit into 32 bits
+    pub fn from_infozip_field(field: &FileHeaderExtraField) -> Option<Self> {
+        let data = &field.data;
+
+        // Only version 1 is defined
+        if data.first() != Some(&1) {
+            return None;
+        }
+
+        let (uid, rest) = Self::read_variable_id(&data[1..])?;
+        let (gid, _) = Self::read_variable_id(rest)?;
+
+        Some(Self {
+            uid,
+            gid
+        })
+    }
+
+    /// Attempts to read a PKWARE Unix (0x000D) extra field, in which the
+    /// UID and GID are 16 bits long. Returns None if it is malformed
+    pub fn from_pkware_field(field: &FileHeaderExtraField) -> Option<Self> {
+        if field.data.len() < PKWARE_UNIX_EXTRA_FIELD_CONSTANT_SIZE {
+            return None;
+        }
+
+        // Skip the access and modification times
+        let mut cursor = Cursor::new(&field.data[8..]);
+
+        let uid = cursor.read_u16::<LittleEndian>().unwrap();
+        let gid = cursor.read_u16::<LittleEndian>().unwrap();
+
+        Some(Self {
+            uid: uid as u32,
+            gid: gid as u32
+        })
+    }
+
+    fn read_variable_id(data: &[u8]) -> Option<(u32, &[u8])> {
+        let size = *data.first()? as usize;
+        if data.len() < 1 + size {
+            return None;
+        }
+
+        let bytes = &data[1..(1 + size)];
+
+        // Allow redundant zero high bytes
+        let significant = bytes.iter().rposition(|b| *b != 0).map_or(0, |p| p + 1);
+        if significant > 4 {
+            return None;
+        }
+
+        let mut id = [0; 4];
+        id[..significant].copy_from_slice(&bytes[..significant]);
+
+        Some((u32::from_le_bytes(id), &data[(1 + size)..]))
+    }
+}
+
+pub const UNICODE_PATH_EXTRA_FIELD_ID: u16 = 0x7075;
+pub const UNICODE_COMMENT_EXTRA_FIELD_ID: u16 = 0x6375;
+pub const UNICODE_EXTRA_FIELD_CONSTANT_SIZE: usize = 5;
+
+/// Determines where a decoded filename or comment was taken from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TextSource {
+    /// The filename or comment field of the header itself
+    Header,
+
+    /// An Info-ZIP Unicode Path or Unicode Comment extra field
+    UnicodeExtraField
+}
+
+/// Contains the data of an Info-ZIP Unicode Path or Unicode Comment extra field
+#[derive(Debug, Clone)]
+pub struct UnicodeExtraField {
+    /// The CRC-32 of the original filename or comment from the header
+    pub crc32: u32,
+
+    pub text: String
+}
+
+impl UnicodeExtraField {
+    /// Attempts to find and read a Unicode extra field with the specified id in the provided 
+    /// fields. Returns None if there isn't one, or if it is malformed or is not valid UTF-8
+    pub fn from_fields(fields: impl AsRef<[FileHeaderExtraField]>, id: u16) -> Option<Self> {
+        fields.as_ref()
+            .iter()
+            .find(|f| f.id == id)
+            .and_then(Self::from_field)
+    }
+
+    /// Attempts to read a Unicode Path or Unicode Comment extra field.
+    /// Returns None if it is malformed or is not valid UTF-8
+    pub fn from_field(field: &FileHeaderExtraField) -> Option<Self> {
+
+        // Only version 1 is defined
+        if field.data.len() < UNICODE_EXTRA_FIELD_CONSTANT_SIZE || field.data[0] != 1 {
+            return None;
+        }
+
+        let crc32 = u32::from_le_bytes(field.data[1..5].try_into().unwrap());
+        let text = String::from_utf8(field.data[UNICODE_EXTRA_FIELD_CONSTANT_SIZE..].to_owned()).ok()?;
+
+        Some(Self {
+            crc32,
+            text
+        })
+    }
+
+    /// Returns whether this field corresponds to the provided original bytes from the header.
+    /// If it doesn't, the header was modified by a tool which is not aware of this field
+    pub fn matches(&self, original: impl AsRef<[u8]>) -> bool {
+        crc32fast::hash(original.as_ref()) == self.crc32
+    }
+}
+
+/// Decodes a filename or comment, preferring the Unicode extra field with the specified
+/// id if it exists and matches the original bytes. See [decode_header_text] for how
+/// the original bytes are decoded otherwise
+pub(crate) fn decode_text(original: &[u8], flag: u16, fields: &[FileHeaderExtraField], unicode_field_id: u16) -> (String, TextSource) {
+    match UnicodeExtraField::from_fields(fields, unicode_field_id) {
+        Some(field) if field.matches(original) => (field.text, TextSource::UnicodeExtraField),
+        _ => (decode_header_text(original, flag & FLAG_UTF8 != 0), TextSource::Header)
+    }
+}
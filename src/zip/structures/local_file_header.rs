@@ -1,12 +1,45 @@
-use std::io::Cursor;
+use std::{io::Cursor, path::PathBuf, time::SystemTime};
 
 use byteorder::{ReadBytesExt, LittleEndian};
 
-use super::{CompressionMethod, file_header::{FileHeaderExtraField, Zip64ProcessedData, Zip64OriginalData}};
+use super::{CompressionMethod, central_directory::CentralDirectoryFileHeader, file_header::{UnsupportedFeature, unsupported_feature, FileHeaderExtraField, Zip64ProcessedData, Zip64OriginalData, FLAG_DATA_DESCRIPTOR, FLAG_ENCRYPTED, ZIP64_EXTRA_FIELD_ID, AesExtraField, ExtendedTimestamp, NtfsTimestamps, UnixOwnership, TextSource, UNICODE_PATH_EXTRA_FIELD_ID, decode_text}, date_time::{ZipDateTime, ZipTimeZone}, extra_field::{ExtraField, ExtraFieldRegistry}, path::{sanitize_path, PathSanitizationError}};
 
 pub const LFH_SIGNATURE: u32 = 0x04034b50;
 pub const LFH_CONSTANT_SIZE: usize = 26;
 
+/// Split and spanned archives may start with one of these markers before the first LFH
+pub const SPANNING_MARKER_SIGNATURE: u32 = 0x08074b50;
+pub const TEMPORARY_SPANNING_MARKER_SIGNATURE: u32 = 0x30304b50;
+pub const SPANNING_MARKER_SIZE: usize = 4;
+
+/// Returns whether the data starts with a spanning marker
+pub fn starts_with_spanning_marker(data: impl AsRef<[u8]>) -> bool {
+    let data = data.as_ref();
+    if data.len() < SPANNING_MARKER_SIZE {
+        return false;
+    }
+
+    let signature = u32::from_le_bytes(data[..SPANNING_MARKER_SIZE].try_into().unwrap());
+    signature == SPANNING_MARKER_SIGNATURE || signature == TEMPORARY_SPANNING_MARKER_SIGNATURE
+}
+
+/// A field which can differ between a local file header
+/// and the corresponding central directory file header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HeaderField {
+    Filename,
+    Flag,
+    CompressionMethod,
+    Crc32,
+    CompressedSize,
+    UncompressedSize,
+
+    /// The CRC-32 or sizes in the data descriptor, which
+    /// follows the file data instead of the LFH
+    DataDescriptor
+}
+
 /// Represents the result of reading a ZIP local file header (LFH)
 /// 
 /// The layout of this object does not follow the original ZIP LFH structure
@@ -27,6 +60,10 @@ pub struct LocalFileHeader {
     pub uncompressed_size: u64,
 
     pub filename: String,
+    pub filename_source: TextSource,
+
+    /// The original bytes of the filename, before any decoding
+    pub filename_raw: Vec<u8>,
     
     pub extra_fields: Vec<FileHeaderExtraField>,
 
@@ -65,13 +102,12 @@ impl LocalFileHeader {
 
         let filename_start = LFH_CONSTANT_SIZE;
         let filename_end = filename_start + filename_length;
-        let filename = String::from_utf8_lossy(&data[filename_start..filename_end]).to_string();
 
         let extra_fields_start = filename_end;
         let extra_fields_end = extra_fields_start + extra_fields_length;
-        let Some(extra_fields) = FileHeaderExtraField::read_extra_fields(&data[extra_fields_start..extra_fields_end]) else {
-            return None;
-        };
+        let extra_fields = FileHeaderExtraField::read_extra_fields(&data[extra_fields_start..extra_fields_end])?;
+
+        let (filename, filename_source) = decode_text(&data[filename_start..filename_end], flag, &extra_fields, UNICODE_PATH_EXTRA_FIELD_ID);
         
         let original_zip64_data = Zip64OriginalData {
             uncompressed_size,
@@ -79,13 +115,11 @@ impl LocalFileHeader {
             ..Default::default()
         };
 
-        let Some(Zip64ProcessedData {
+        let Zip64ProcessedData {
             uncompressed_size,
             compressed_size,
             ..
-        }) = original_zip64_data.process(&extra_fields) else {
-            return None;
-        };
+        } = original_zip64_data.process(&extra_fields)?;
 
         Some(Self {
             version,
@@ -97,6 +131,8 @@ impl LocalFileHeader {
             compressed_size,
             uncompressed_size,
             filename,
+            filename_source,
+            filename_raw: data[filename_start..filename_end].to_owned(),
             extra_fields,
 
             header_size: extra_fields_end
@@ -106,4 +142,111 @@ impl LocalFileHeader {
     pub fn is_directory(&self) -> bool {
         self.filename.ends_with('/')
     }
+
+    /// Returns the filename as a relative path which can be safely joined with
+    /// an output directory. See [sanitize_path] for details
+    pub fn sanitized_path(&self) -> Result<PathBuf, PathSanitizationError> {
+        sanitize_path(&self.filename)
+    }
+
+    /// Compares this LFH with the corresponding CDFH and returns the fields
+    /// which differ. The CRC-32 and sizes are not compared if this file has a
+    /// data descriptor, since they are not stored in the LFH then
+    pub fn mismatches(&self, cdfh: &CentralDirectoryFileHeader) -> Vec<HeaderField> {
+        let mut mismatches = Vec::new();
+
+        if self.filename_raw != cdfh.filename_raw {
+            mismatches.push(HeaderField::Filename);
+        }
+
+        if self.flag != cdfh.flag {
+            mismatches.push(HeaderField::Flag);
+        }
+
+        let method_id = |m: &Option<CompressionMethod>| m.as_ref().map_or(0, CompressionMethod::id);
+        if method_id(&self.compression_method) != method_id(&cdfh.compression_method) {
+            mismatches.push(HeaderField::CompressionMethod);
+        }
+
+        if !self.has_data_descriptor() {
+            if self.crc32 != cdfh.crc32 {
+                mismatches.push(HeaderField::Crc32);
+            }
+
+            if self.compressed_size != cdfh.compressed_size {
+                mismatches.push(HeaderField::CompressedSize);
+            }
+
+            if self.uncompressed_size != cdfh.uncompressed_size {
+                mismatches.push(HeaderField::UncompressedSize);
+            }
+        }
+
+        mismatches
+    }
+
+    /// Returns whether this file is encrypted
+    pub fn is_encrypted(&self) -> bool {
+        self.flag & FLAG_ENCRYPTED != 0
+    }
+
+    /// Returns the unsupported feature used by this file, if there is one
+    pub fn unsupported_feature(&self) -> Option<UnsupportedFeature> {
+        unsupported_feature(self.flag)
+    }
+
+    /// Parses all extra fields of this file using the provided registry.
+    /// Use [ExtraFieldRegistry::default] to only parse the well-known fields
+    pub fn parsed_extra_fields(&self, registry: &ExtraFieldRegistry) -> Vec<ExtraField> {
+        registry.parse_all(&self.extra_fields, false)
+    }
+
+    /// Returns the WinZip AES extra field of this file, if there is one.
+    /// If there is, the actual compression method is stored in it
+    pub fn aes_extra_field(&self) -> Option<AesExtraField> {
+        AesExtraField::from_fields(&self.extra_fields)
+    }
+
+    /// Returns the extended timestamp extra field of this file, if there is one
+    pub fn extended_timestamp(&self) -> Option<ExtendedTimestamp> {
+        ExtendedTimestamp::from_fields(&self.extra_fields, false)
+    }
+
+    /// Returns the timestamps from the NTFS extra field of this file, if there is one
+    pub fn ntfs_timestamps(&self) -> Option<NtfsTimestamps> {
+        NtfsTimestamps::from_fields(&self.extra_fields)
+    }
+
+    /// Returns the owner of this file from the Info-ZIP or PKWARE Unix extra field, if there is one
+    pub fn unix_ownership(&self) -> Option<UnixOwnership> {
+        UnixOwnership::from_fields(&self.extra_fields)
+    }
+
+    /// Returns the DOS modification date and time of this file, or None if it is invalid
+    pub fn date_time(&self) -> Option<ZipDateTime> {
+        ZipDateTime::from_dos(self.mod_date, self.mod_time)
+    }
+
+    /// Returns the modification time of this file. It is taken from the NTFS or
+    /// the extended timestamp extra field if there is one, otherwise the DOS time is
+    /// used, interpreted in the specified time zone (the extra fields are always UTC)
+    pub fn modification_time(&self, time_zone: ZipTimeZone) -> Option<SystemTime> {
+        self.ntfs_timestamps()
+            .map(|t| t.modification_time)
+            .or_else(|| self.extended_timestamp().and_then(|t| t.modification_time))
+            .or_else(|| self.date_time().map(|t| t.to_system_time(time_zone)))
+    }
+
+    /// Returns whether the sizes and CRC-32 of this file are stored in a
+    /// data descriptor after the file data. If so, they are zero in this header
+    /// and must be taken from the central directory
+    pub fn has_data_descriptor(&self) -> bool {
+        self.flag & FLAG_DATA_DESCRIPTOR != 0
+    }
+
+    /// Returns whether this header contains a ZIP64 extra field. This
+    /// also determines the size of the data descriptor, if there is one
+    pub fn is_zip64(&self) -> bool {
+        self.extra_fields.iter().any(|f| f.id == ZIP64_EXTRA_FIELD_ID)
+    }
 }
@@ -11,12 +11,30 @@ pub mod cd_location;
 /// Provides general ZIP file header utilities
 pub mod file_header;
 
+/// Provides utilities for parsing ZIP file header extra fields into typed values
+pub mod extra_field;
+
 /// Provides utilities for processing ZIP central directory file headers
 pub mod central_directory;
 
 /// Provides utilities for processing ZIP local file headers
 pub mod local_file_header;
 
+/// Provides utilities for processing ZIP data descriptors
+pub mod data_descriptor;
+
+/// Provides utilities for decoding ZIP filenames and comments
+pub mod encoding;
+
+/// Provides utilities for working with ZIP dates and times
+pub mod date_time;
+
+/// Provides utilities for interpreting ZIP file attributes
+pub mod attributes;
+
+/// Provides utilities for safely converting ZIP filenames into paths
+pub mod path;
+
 #[derive(Error, Debug)]
 pub enum DecompressorCreationError {
     #[error("unknown compression method: {0}")]
@@ -61,6 +79,16 @@ impl CompressionMethod {
         }
     }
 
+    /// Returns the ZIP compression id of this method
+    pub fn id(&self) -> u16 {
+        match self {
+            #[cfg(feature = "deflate")]
+            Self::Deflate => 8,
+
+            Self::Unknown(id) => *id
+        }
+    }
+
     /// Returns whether decompression is supported for this method
     pub fn is_supported(&self) -> bool {
         !(matches!(self, Self::Unknown(..)))
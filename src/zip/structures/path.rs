@@ -0,0 +1,126 @@
+use std::path::{Component, Path, PathBuf};
+
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PathSanitizationError {
+    #[error("path contains a NUL byte")]
+    NulByte,
+
+    #[error("path contains a parent directory component")]
+    ParentDirectory,
+
+    #[error("path contains an invalid component: {0}")]
+    InvalidComponent(String),
+
+    #[error("path does not contain any normal components")]
+    Empty
+}
+
+/// Converts a filename into a relative path which can be safely joined
+/// with an output directory. Backslashes are treated as separators, leading
+/// slashes, a leading drive letter and "." components are stripped. Names containing
+/// ".." components, NUL bytes, colons or other components which are not plain names
+/// on the current platform are rejected. Colons are rejected on every platform, even
+/// where they are valid in filenames, so that the result is also safe on Windows,
+/// where they denote drive letters and alternate data streams
+pub fn sanitize_path(filename: &str) -> Result<PathBuf, PathSanitizationError> {
+    if filename.contains('\0') {
+        return Err(PathSanitizationError::NulByte);
+    }
+
+    let filename = filename.replace('\\', "/");
+    let filename = strip_drive_letter(&filename);
+
+    let mut path = PathBuf::new();
+    for component in filename.split('/') {
+        match component {
+            "" | "." => continue,
+            ".." => return Err(PathSanitizationError::ParentDirectory),
+            _ if !is_normal_component(component) => return Err(PathSanitizationError::InvalidComponent(component.to_owned())),
+            _ => path.push(component)
+        }
+    }
+
+    if path.as_os_str().is_empty() {
+        return Err(PathSanitizationError::Empty);
+    }
+
+    Ok(path)
+}
+
+/// Pushing anything other than a single normal component (for example, a
+/// prefix like "C:" on Windows) could replace the whole path. Colons are
+/// rejected regardless of the platform for portability
+fn is_normal_component(component: &str) -> bool {
+    if component.contains(':') {
+        return false;
+    }
+
+    let mut components = Path::new(component).components();
+    matches!((components.next(), components.next()), (Some(Component::Normal(_)), None))
+}
+
+fn strip_drive_letter(filename: &str) -> &str {
+    let bytes = filename.as_bytes();
+    if bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' {
+        &filename[2..]
+    } else {
+        filename
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{sanitize_path, PathSanitizationError};
+
+    #[test]
+    fn keeps_relative_paths() {
+        assert_eq!(sanitize_path("dir/file.txt"), Ok(PathBuf::from("dir").join("file.txt")));
+        assert_eq!(sanitize_path("dir/./file.txt"), Ok(PathBuf::from("dir").join("file.txt")));
+        assert_eq!(sanitize_path("dir/"), Ok(PathBuf::from("dir")));
+        assert_eq!(sanitize_path("..file"), Ok(PathBuf::from("..file")));
+    }
+
+    #[test]
+    fn rejects_parent_directories() {
+        assert_eq!(sanitize_path("../../etc/passwd"), Err(PathSanitizationError::ParentDirectory));
+        assert_eq!(sanitize_path("dir/../../file"), Err(PathSanitizationError::ParentDirectory));
+        assert_eq!(sanitize_path("dir\\..\\..\\file"), Err(PathSanitizationError::ParentDirectory));
+    }
+
+    #[test]
+    fn strips_absolute_paths() {
+        assert_eq!(sanitize_path("/etc/passwd"), Ok(PathBuf::from("etc").join("passwd")));
+        assert_eq!(sanitize_path("\\\\server\\share\\file"), Ok(PathBuf::from("server").join("share").join("file")));
+        assert_eq!(sanitize_path("/"), Err(PathSanitizationError::Empty));
+    }
+
+    #[test]
+    fn strips_leading_drive_letters() {
+        assert_eq!(sanitize_path("C:\\x"), Ok(PathBuf::from("x")));
+        assert_eq!(sanitize_path("C:/x/y"), Ok(PathBuf::from("x").join("y")));
+        assert_eq!(sanitize_path("C:x"), Ok(PathBuf::from("x")));
+        assert_eq!(sanitize_path("C:"), Err(PathSanitizationError::Empty));
+    }
+
+    #[test]
+    fn rejects_drive_letters_in_the_middle() {
+        assert_eq!(sanitize_path("x/C:/Windows/evil.dll"), Err(PathSanitizationError::InvalidComponent("C:".to_owned())));
+        assert_eq!(sanitize_path("x\\C:\\evil.dll"), Err(PathSanitizationError::InvalidComponent("C:".to_owned())));
+        assert_eq!(sanitize_path("file.txt:stream"), Err(PathSanitizationError::InvalidComponent("file.txt:stream".to_owned())));
+    }
+
+    #[test]
+    fn rejects_colons_on_every_platform() {
+        assert_eq!(sanitize_path("logs/12:30.log"), Err(PathSanitizationError::InvalidComponent("12:30.log".to_owned())));
+    }
+
+    #[test]
+    fn rejects_nul_bytes() {
+        assert_eq!(sanitize_path("file\0.txt"), Err(PathSanitizationError::NulByte));
+    }
+}